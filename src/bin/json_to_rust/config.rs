@@ -0,0 +1,245 @@
+use anyhow::Context as _;
+use json::JsonValue;
+use json_to_rust::Override;
+use std::ffi::OsString;
+
+/// the long name, the short name and whether the flag takes a value
+const FLAGS: &[(&str, Option<&str>, bool)] = &[
+    ("make-unit-tests", Some("-u"), false),
+    ("make-main", Some("-m"), false),
+    ("json-root-name", Some("-j"), true),
+    ("rust-root-name", Some("-n"), true),
+    ("max-tuple", Some("-t"), true),
+    ("integers", Some("-i"), true),
+    ("precision", Some("-p"), true),
+    ("unify", None, true),
+    ("json-lines", Some("-l"), false),
+    ("schema", None, false),
+    ("derive", Some("-d"), true),
+    ("no-std-derives", Some("-nd"), false),
+    ("field-naming", Some("-f"), true),
+    ("struct-naming", Some("-s"), true),
+    ("vec-wrapper", None, true),
+    ("map-wrapper", None, true),
+    ("placeholder", None, true),
+    ("flatten-option-vec", None, false),
+    ("no-recursion", None, false),
+    ("no-maps", None, false),
+    ("map-threshold", None, true),
+    ("merge-similar", None, true),
+    ("cluster", None, true),
+    ("flatten-shared", None, true),
+    ("generics", None, false),
+    ("required-threshold", None, true),
+    ("presence-comments", None, false),
+    ("double-option", None, false),
+    ("format", None, true),
+    ("dates", None, true),
+    ("enum-limit", None, true),
+    ("enum-unknown", None, false),
+    ("tag-key", None, true),
+    ("no-tagged-enums", None, false),
+    ("no-external-tags", None, false),
+];
+
+/// reads the config file, turning its settings into arguments
+///
+/// settings for flags that were already `given` are skipped, so the command line takes precedence
+pub fn load(path: &str, given: &[OsString]) -> anyhow::Result<(Vec<OsString>, Vec<Override>)> {
+    let data =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read config '{}'", path))?;
+    let config = json::parse(&data).with_context(|| format!("invalid config '{}'", path))?;
+    anyhow::ensure!(config.is_object(), "config '{}' should be an object", path);
+
+    let (mut args, mut overrides) = (Vec::new(), Vec::new());
+    for (key, value) in config.entries() {
+        if key == "paths" {
+            overrides = parse_paths(value)?;
+            continue;
+        }
+
+        let (long, short, takes_value) = FLAGS
+            .iter()
+            .find(|(long, ..)| *long == key)
+            .with_context(|| format!("unknown setting '{}' in the config", key))?;
+
+        let flag = format!("--{}", long);
+        if given
+            .iter()
+            .any(|arg| *arg == *flag || matches!(short, Some(short) if arg == short))
+        {
+            continue;
+        }
+
+        match (value, takes_value) {
+            (JsonValue::Boolean(true), false) => args.push(flag.into()),
+            (JsonValue::Boolean(false), false) => {}
+            (JsonValue::Array(values), true) => {
+                for value in values {
+                    args.push(flag.clone().into());
+                    args.push(parse_value(key, value)?.into());
+                }
+            }
+            (value, true) => {
+                args.push(flag.into());
+                args.push(parse_value(key, value)?.into());
+            }
+            _ => anyhow::bail!("'{}' should be true or false", key),
+        }
+    }
+
+    Ok((args, overrides))
+}
+
+fn parse_value(key: &str, value: &JsonValue) -> anyhow::Result<String> {
+    match value {
+        value if value.is_number() => Ok(value.dump()),
+        value => value
+            .as_str()
+            .map(ToString::to_string)
+            .with_context(|| format!("'{}' should be a string or a number", key)),
+    }
+}
+
+fn parse_paths(paths: &JsonValue) -> anyhow::Result<Vec<Override>> {
+    const KEYS: &[&str] = &["type", "name", "rename", "map", "raw"];
+    anyhow::ensure!(paths.is_object(), "'paths' should be an object");
+
+    paths
+        .entries()
+        .map(|(path, settings)| {
+            anyhow::ensure!(settings.is_object(), "'{}' should be an object", path);
+            if let Some((key, _)) = settings.entries().find(|(key, _)| !KEYS.contains(key)) {
+                anyhow::bail!("unknown setting '{}' for '{}'", key, path);
+            }
+
+            let text = |key: &str| match &settings[key] {
+                JsonValue::Null => Ok(None),
+                value => value
+                    .as_str()
+                    .map(|s| Some(s.to_string()))
+                    .with_context(|| format!("'{}' for '{}' should be a string", key, path)),
+            };
+            let flag = |key: &str| match &settings[key] {
+                JsonValue::Null => Ok(false),
+                JsonValue::Boolean(flag) => Ok(*flag),
+                _ => anyhow::bail!("'{}' for '{}' should be true or false", key, path),
+            };
+
+            Ok(Override {
+                path: path.to_string(),
+                kind: text("type")?,
+                name: text("name")?,
+                rename: text("rename")?,
+                map: flag("map")?,
+                raw: flag("raw")?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn load_str(config: &str, given: &[&str]) -> anyhow::Result<(Vec<OsString>, Vec<Override>)> {
+        // the tests run in parallel, so every config gets its own file
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "json_to_rust_config_{}_{}.json",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, config).unwrap();
+        let given = given.iter().map(OsString::from).collect::<Vec<_>>();
+        let loaded = load(path.to_str().unwrap(), &given);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn settings() {
+        let config = r#"{
+            "generics": true,
+            "double-option": false,
+            "rust-root-name": "Root",
+            "map-threshold": 8,
+            "tag-key": ["type", "kind"]
+        }"#;
+        let (args, overrides) = load_str(config, &[]).unwrap();
+        let expected = [
+            "--generics",
+            "--rust-root-name",
+            "Root",
+            "--map-threshold",
+            "8",
+            "--tag-key",
+            "type",
+            "--tag-key",
+            "kind",
+        ];
+        assert_eq!(
+            args,
+            expected.iter().map(OsString::from).collect::<Vec<_>>()
+        );
+        assert!(overrides.is_empty());
+
+        // the command line wins, with either the long or the short flag
+        let (args, _) = load_str(config, &["-n", "Other", "--tag-key", "kind"]).unwrap();
+        let expected = ["--generics", "--map-threshold", "8"];
+        assert_eq!(
+            args,
+            expected.iter().map(OsString::from).collect::<Vec<_>>()
+        );
+
+        assert!(load_str(r#"{"unknown": true}"#, &[]).is_err());
+        assert!(load_str(r#"{"generics": "yes"}"#, &[]).is_err());
+        assert!(load_str(r#"{"rust-root-name": true}"#, &[]).is_err());
+        assert!(load_str(r#"[]"#, &[]).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let paths = json::parse(
+            r#"{
+                "$.items[].id": {"type": "uuid::Uuid", "rename": "identifier"},
+                "$.meta": {"name": "Metadata", "map": true, "raw": false}
+            }"#,
+        )
+        .unwrap();
+        let expected = vec![
+            Override {
+                path: "$.items[].id".into(),
+                kind: Some("uuid::Uuid".into()),
+                rename: Some("identifier".into()),
+                ..Override::default()
+            },
+            Override {
+                path: "$.meta".into(),
+                name: Some("Metadata".into()),
+                map: true,
+                ..Override::default()
+            },
+        ];
+        assert_eq!(parse_paths(&paths).unwrap(), expected);
+
+        for paths in &[
+            r#"[]"#,
+            r#"{"$.a": true}"#,
+            r#"{"$.a": {"kind": "u8"}}"#,
+            r#"{"$.a": {"type": 1}}"#,
+            r#"{"$.a": {"map": "yes"}}"#,
+        ] {
+            assert!(
+                parse_paths(&json::parse(paths).unwrap()).is_err(),
+                "{}",
+                paths
+            );
+        }
+
+        let (_, overrides) = load_str(r#"{"paths": {"$.a": {"raw": true}}}"#, &[]).unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert!(overrides[0].raw);
+    }
+}
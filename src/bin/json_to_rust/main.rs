@@ -1,5 +1,12 @@
+use anyhow::Context as _;
 use inflections::Inflect as _;
-use json_to_rust::{all_std_derives, custom, no_derives, CasingScheme, Wrapper};
+use json_to_rust::{
+    all_std_derives, custom, no_derives, CasingScheme, DateLibrary, InputMode, IntegerPolicy, Kind,
+    Precision, Resolution, StringFormat, UnifyPolicy, Wrapper,
+};
+use std::{ffi::OsString, fs::File, io::Read};
+
+mod config;
 
 fn header() {
     println!("{}: {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
@@ -17,6 +24,7 @@ description:
 
 usage:
     cat foo.json | json_to_rust -j json_object -n MyStruct > out.rs
+    json_to_rust -n MyStruct first.json second.json > out.rs
 
 flags:
     -u, --make-unit-tests   generate unit tests
@@ -27,6 +35,14 @@ flags:
 
     -t, --max-tuple         heterogeneous arrays under this size will be treated as a tuple
 
+    -i, --integers          how the integer types are chosen
+    -p, --precision         the type used for numbers that don't fit in an i64, u64 or f64
+    --unify                 how values that were seen with different kinds of types are unified
+
+    -l, --json-lines        treat each line of the input as its own json document
+    --schema                treat the input as a json schema rather than a sample
+    --config                read the settings from this json file
+
     -d, --derive            add this derive to the generate types
     -nd, --no-std-derives   only use the serde derives
 
@@ -35,8 +51,30 @@ flags:
 
     --vec-wrapper           use this type for Vecs, defaults to 'Vec'
     --map-wrapper           use this type for Maps, defaults to 'HashMap'
+    --placeholder           use this type for values that were never observed
 
     --flatten-option-vec    flattens Option<Vec<T>> into just Vec<T>
+    --no-recursion          don't use recursive types for objects that nest themselves
+
+    --no-maps               don't infer maps for objects with dynamic keys
+    --map-threshold         objects with at least this many keys of the same type will be a map
+    --merge-similar         merge sibling objects that share at least this ratio of their keys
+    --cluster               split objects in an array that share less than this ratio of their keys
+    --flatten-shared        move at least this many fields that structs share into a flattened struct
+    --generics              use a generic struct for structs that only differ in the type of one field
+
+    --required-threshold    fields present in at least this ratio of the objects won't be optional
+    --presence-comments     add a comment to each field with how often it was present
+    --double-option         use Option<Option<T>> for fields that were both missing and null
+
+    --format                detect strings of this format and use a type for them
+    --dates                 detect dates and timestamps and use this library for them
+    --enum-limit            strings with at most this many distinct values will be an enum
+    --enum-unknown          add an `Unknown` catch-all variant to the generated enums
+
+    --tag-key               the field used to detect internally tagged enums
+    --no-tagged-enums       don't detect internally tagged enums
+    --no-external-tags      don't detect externally tagged enums, e.g. `{"Circle": {"r": 1}}`
 
     -v, --version           show the current version
     -h, --help              show this message
@@ -54,6 +92,12 @@ description:
 
 usage:
     cat foo.json | json_to_rust -j json_object -n MyStruct > out.rs
+    json_to_rust -n MyStruct first.json second.json > out.rs
+
+    if files are provided they'll be used as samples instead of stdin.
+    all of the samples are merged into a single set of types,
+    fields that aren't in every sample will be optional.
+    a file name of '-' will read from stdin
 
 flags:
     -u, --make-unit-tests   generate unit tests
@@ -76,6 +120,54 @@ flags:
                             - for types such as [1, false, "foo"] if the length exceeds the provided value
                             - then a Vec<Value> will be created instead. otherwise a tuple will be created.
                             - for the example above: a tuple of (i64, bool, String)
+                            - a tuple struct named after the field is used, e.g. pub struct Row(i64, bool, String);
+                            - shorter arrays make the trailing elements optional, e.g. Option<String>
+
+    -i, --integers          how the integer types are chosen
+                            - this defaults to i64
+                            - available options [i64, smallest, unsigned]
+                            - smallest uses the smallest type that fits every observed value
+                            - unsigned uses u64 if every observed value is non-negative
+
+    -p, --precision         the type used for numbers that don't fit in an i64, u64 or f64
+                            - this defaults to lossy, which just uses i64 or f64
+                            - available options [lossy, wide, decimal, arbitrary]
+                            - wide uses i128 or u128 for integers. decimals stay f64
+                            - integers that fit in a u64 still use u64
+                            - decimal uses rust_decimal::Decimal
+                            - arbitrary uses serde_json::Number
+
+    --unify                 how values that were seen with different kinds of types are unified
+                            - this flag can be used multiple times, as kinds=resolution
+                            - kinds are a pair like number|string, or * for every other pair
+                            - available kinds [bool, number, string, array, object]
+                            - available resolutions [union, value, error] or one of the two kinds
+                            - union keeps both types as an untagged enum, this is the default
+                            - value uses serde_json::Value
+                            - error fails and shows where both types were seen in the samples
+                            - e.g. --unify number|string=string --unify bool|number=number --unify *=error
+
+    -l, --json-lines        treat each line of the input as its own json document
+                            - this is for NDJSON / JSON Lines input
+                            - all of the lines are merged into a single root type
+                            - fields missing from some of the lines will be optional
+
+    --schema                treat the input as a json schema rather than a sample
+                            - draft 7 and 2020-12 schemas are supported
+                            - required properties are fields, the other properties are optional
+                            - enum is an enum, oneOf and anyOf are an untagged enum
+                            - definitions used with $ref are a named type, e.g. #/$defs/Address
+                            - additionalProperties without properties is a map
+
+    --config                read the settings from this json file
+                            - the keys are the long names of the flags, e.g. { "integers": "smallest", "no-maps": true }
+                            - flags that can be repeated use an array, e.g. { "format": ["uuid", "url"] }
+                            - flags given on the command line take precedence over the file
+                            - "paths" has settings for single nodes, addressed by their json path:
+                              { "paths": { "$.data.items[].created_at": { "type": "my::Timestamp" } } }
+                            - "type" uses this type, "name" names the generated type, "rename" renames the field
+                            - "map": true treats the object as a map, "raw": true keeps it as a serde_json::Value
+                            - array elements are [], map values are [*] and tuple elements are [0], [1], ...
 
     -d, --derive            add this derive to the generate types
                             - this can accept a string or a comma seperated string.
@@ -100,9 +192,94 @@ flags:
     --vec-wrapper           use this type for Vecs, defaults to 'Vec'
     --map-wrapper           use this type for Maps, defaults to 'HashMap'
 
+    --placeholder           use this type for values that were never observed
+                            - e.g. the items of an empty array, or a field that was always null
+                            - by default these get a placeholder alias for serde_json::Value
+                            - e.g. pub type TagsItem = ::serde_json::Value; // TODO: was never observed
+                            - either way, their json paths are reported on stderr
+
     --flatten-option-vec    flattens Option<Vec<T>> into just Vec<T>
                             - this also uses serde_default which'll create an empty Vec if it was None
 
+    --no-recursion          don't use recursive types for objects that nest themselves
+                            - e.g. trees, where "children" has objects with their own "children"
+                            - these will be a single type using Vec<Self> or Option<Box<Self>>
+
+    --no-maps               don't infer maps for objects with dynamic keys
+                            - objects where every key looks like an id, uuid, hash or date
+                            - will be a map (using --map-wrapper) rather than a struct
+
+    --map-threshold         objects with at least this many keys of the same type will be a map
+                            - this defaults to 32
+
+    --merge-similar         merge sibling objects that share at least this ratio of their keys
+                            - this is disabled by default
+                            - e.g. 0.8 means 80% of the keys across both objects are shared
+                            - keys that are only in some of the objects will be optional
+                            - the merged paths are reported on stderr
+
+    --cluster               split objects in an array that share less than this ratio of their keys
+                            - this is disabled by default
+                            - each group of objects will be its own struct
+                            - the array will be a Vec of an untagged enum of those structs
+                            - each variant is named after a key that only its objects have
+                            - the variants are ordered so the struct with the most required fields is tried first
+
+    --flatten-shared        move at least this many fields that structs share into a flattened struct
+                            - this is disabled by default
+                            - e.g. with 3, structs that all have `id`, `created_at` and `updated_at`
+                            - will embed a `Shared` struct with those fields using #[serde(flatten)]
+
+    --generics              use a generic struct for structs that only differ in the type of one field
+                            - e.g. pagination envelopes with a different type of item in `data`
+                            - e.g. `UserPage` and `PostPage` become a single `Page<T>`
+                            - which is used as `Page<User>`, `Page<Post>`, ...
+
+    --required-threshold    fields present in at least this ratio of the objects won't be optional
+                            - by default a field is optional if it was missing from any object
+                            - e.g. 0.99 means a field missing from 1% of the objects is still required
+                            - fields that were ever null are always optional
+                            - the fields made required are reported on stderr
+
+    --presence-comments     add a comment to each field with how often it was present
+                            - and how often it was null
+
+    --double-option         use Option<Option<T>> for fields that were both missing and null
+                            - a missing field is None and a null field is Some(None)
+                            - this uses serde_with::rust::double_option, which requires the serde_with crate
+
+    --format                detect strings of this format and use a type for them
+                            - this flag can be used multiple times
+                            - available formats [uuid, url, ip, email]
+                            - a type can be provided with format=type, e.g. --format uuid=my::Uuid
+                            - the defaults are uuid::Uuid, url::Url, std::net::IpAddr
+                            - and email_address::EmailAddress
+                            - every observed value has to match, otherwise it'll be a String
+
+    --dates                 detect dates and timestamps and use this library for them
+                            - this is disabled by default
+                            - available options [chrono, time]
+                            - RFC 3339 strings and plain dates (2020-10-17) are detected
+                            - integers are detected as unix timestamps (seconds or milliseconds)
+                            - if the field name ends in at, on, time, timestamp, ts, date or ms
+
+    --enum-limit            strings with at most this many distinct values will be an enum
+                            - this is disabled by default
+                            - the values have to repeat, otherwise they'll be a String
+                            - variants will be renamed to the original string
+
+    --enum-unknown          add an `Unknown` catch-all variant to the generated enums
+                            - this uses #[serde(other)] so unseen values can be deserialized
+
+    --tag-key               the field used to detect internally tagged enums
+                            - this flag can be used multiple times, the first key found is used
+                            - this defaults to 'type' and 'kind'
+                            - objects with mostly different fields for each value of the tag will
+                            - be an enum using #[serde(tag = "...")] with a struct per value
+
+    --no-tagged-enums       don't detect internally tagged enums
+    --no-external-tags      don't detect externally tagged enums, e.g. `{"Circle": {"r": 1}}`
+
     -v, --version           show the current version
     -h, --help              show this message
         "#;
@@ -127,8 +304,155 @@ fn parse_casing(input: &str) -> Result<CasingScheme, pico_args::Error> {
     Ok(ok)
 }
 
-fn parse_args() -> anyhow::Result<json_to_rust::Options> {
-    let mut args = pico_args::Arguments::from_env();
+fn parse_integers(input: &str) -> Result<IntegerPolicy, pico_args::Error> {
+    let ok = match input.to_lower_case().as_str() {
+        "i64" => IntegerPolicy::I64,
+        "smallest" => IntegerPolicy::Smallest,
+        "unsigned" => IntegerPolicy::Unsigned,
+        s => {
+            let cause = format!("'{}' unknown policy. try [i64,smallest,unsigned]", s);
+            let err = pico_args::Error::ArgumentParsingFailed { cause };
+            return Err(err);
+        }
+    };
+    Ok(ok)
+}
+
+fn parse_precision(input: &str) -> Result<Precision, pico_args::Error> {
+    let ok = match input.to_lower_case().as_str() {
+        "lossy" => Precision::Lossy,
+        "wide" => Precision::Wide,
+        "decimal" => Precision::Decimal,
+        "arbitrary" => Precision::Arbitrary,
+        s => {
+            let cause = format!(
+                "'{}' unknown precision. try [lossy,wide,decimal,arbitrary]",
+                s
+            );
+            let err = pico_args::Error::ArgumentParsingFailed { cause };
+            return Err(err);
+        }
+    };
+    Ok(ok)
+}
+
+fn parse_unify(input: &str) -> Result<(Option<(Kind, Kind)>, Resolution), pico_args::Error> {
+    fn parse_kind(input: &str) -> Result<Kind, pico_args::Error> {
+        let ok = match input.trim().to_lower_case().as_str() {
+            "bool" => Kind::Bool,
+            "number" => Kind::Number,
+            "string" => Kind::String,
+            "array" => Kind::Array,
+            "object" => Kind::Object,
+            s => {
+                let cause = format!(
+                    "'{}' unknown kind. try [bool,number,string,array,object]",
+                    s
+                );
+                let err = pico_args::Error::ArgumentParsingFailed { cause };
+                return Err(err);
+            }
+        };
+        Ok(ok)
+    }
+
+    let (kinds, resolution) = match input.split_once('=') {
+        Some(split) => split,
+        None => {
+            let cause = format!(
+                "'{}' should be kinds=resolution, e.g. number|string=string",
+                input
+            );
+            return Err(pico_args::Error::ArgumentParsingFailed { cause });
+        }
+    };
+
+    let kinds = match kinds.trim() {
+        "*" => None,
+        kinds => match kinds.split_once('|') {
+            Some((left, right)) => Some((parse_kind(left)?, parse_kind(right)?)),
+            None => {
+                let cause = format!("'{}' should be a pair of kinds, e.g. number|string", kinds);
+                return Err(pico_args::Error::ArgumentParsingFailed { cause });
+            }
+        },
+    };
+
+    let resolution = match resolution.trim().to_lower_case().as_str() {
+        "union" => Resolution::Union,
+        "value" => Resolution::Value,
+        "error" => Resolution::Error,
+        kind => Resolution::Prefer(parse_kind(kind)?),
+    };
+
+    if let (Some((left, right)), Resolution::Prefer(kind)) = (kinds, resolution) {
+        if kind != left && kind != right {
+            let cause = format!("'{}' can only use one of its two kinds", input);
+            return Err(pico_args::Error::ArgumentParsingFailed { cause });
+        }
+    }
+
+    Ok((kinds, resolution))
+}
+
+fn parse_format(input: &str) -> Result<(StringFormat, String), pico_args::Error> {
+    let mut iter = input.splitn(2, '=');
+    let format = match iter.next().unwrap_or_default().to_lower_case().as_str() {
+        "uuid" => StringFormat::Uuid,
+        "url" => StringFormat::Url,
+        "ip" => StringFormat::Ip,
+        "email" => StringFormat::Email,
+        s => {
+            let cause = format!("'{}' unknown format. try [uuid,url,ip,email]", s);
+            let err = pico_args::Error::ArgumentParsingFailed { cause };
+            return Err(err);
+        }
+    };
+
+    let ty = iter.next().unwrap_or_else(|| format.default_type());
+    Ok((format, ty.to_string()))
+}
+
+fn parse_dates(input: &str) -> Result<DateLibrary, pico_args::Error> {
+    let ok = match input.to_lower_case().as_str() {
+        "chrono" => DateLibrary::Chrono,
+        "time" => DateLibrary::Time,
+        s => {
+            let cause = format!("'{}' unknown library. try [chrono,time]", s);
+            let err = pico_args::Error::ArgumentParsingFailed { cause };
+            return Err(err);
+        }
+    };
+    Ok(ok)
+}
+
+// removes `--config <file>` from the arguments
+fn take_config(args: &mut Vec<OsString>) -> anyhow::Result<Option<String>> {
+    let pos = match args.iter().position(|arg| arg == "--config") {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+
+    args.remove(pos);
+    anyhow::ensure!(pos < args.len(), "--config requires a file");
+    let path = args.remove(pos);
+    path.into_string()
+        .map(Some)
+        .map_err(|path| anyhow::anyhow!("invalid config path: {:?}", path))
+}
+
+fn parse_args() -> anyhow::Result<(json_to_rust::Options, Vec<String>)> {
+    let mut raw = std::env::args_os().skip(1).collect::<Vec<_>>();
+    let overrides = match take_config(&mut raw)? {
+        Some(path) => {
+            let (args, overrides) = config::load(&path, &raw)?;
+            raw.extend(args);
+            overrides
+        }
+        None => vec![],
+    };
+
+    let mut args = pico_args::Arguments::from_vec(raw);
 
     match (
         args.contains(["-v", "--version"]),
@@ -144,11 +468,37 @@ fn parse_args() -> anyhow::Result<json_to_rust::Options> {
     let json_name = args.opt_value_from_str(["-j", "--json-root-name"])?;
 
     let opts = json_to_rust::Options {
+        input_mode: if args.contains(["-l", "--json-lines"]) {
+            InputMode::JsonLines
+        } else if args.contains("--schema") {
+            InputMode::Schema
+        } else {
+            InputMode::Json
+        },
+
         make_unit_test: args.contains(["-u", "--make-unit-tests"]),
         make_main: args.contains(["-m", "--make-main"]),
 
         tuple_max: args.opt_value_from_str(["-t", "--max-tuple"])?,
 
+        integer_policy: args
+            .opt_value_from_fn(["-i", "--integers"], parse_integers)?
+            .unwrap_or(IntegerPolicy::I64),
+
+        precision: args
+            .opt_value_from_fn(["-p", "--precision"], parse_precision)?
+            .unwrap_or(Precision::Lossy),
+        unify_policy: args
+            .values_from_fn("--unify", parse_unify)?
+            .into_iter()
+            .fold(UnifyPolicy::default(), |mut policy, (kinds, resolution)| {
+                match kinds {
+                    Some((left, right)) => policy.rules.push((left, right, resolution)),
+                    None => policy.fallback = resolution,
+                }
+                policy
+            }),
+
         root_name: args
             .opt_value_from_str(["-n", "--rust-root-name"])?
             .or_else(|| json_name.as_ref().map(|s: &String| s.to_pascal_case()))
@@ -162,21 +512,50 @@ fn parse_args() -> anyhow::Result<json_to_rust::Options> {
                 .as_slice()
             {
                 [] => all_std_derives(),
-                [list @ ..] => custom(list),
+                list => custom(list),
             }
         },
 
         json_name,
 
         collapse_option_vec: args.contains("--flatten-option-vec"),
+        detect_recursion: !args.contains("--no-recursion"),
+
+        infer_maps: !args.contains("--no-maps"),
+        map_threshold: args.opt_value_from_str("--map-threshold")?.unwrap_or(32),
+
+        merge_similar: args.opt_value_from_str("--merge-similar")?,
+        cluster: args.opt_value_from_str("--cluster")?,
+        flatten_shared: args.opt_value_from_str("--flatten-shared")?,
+        generics: args.contains("--generics"),
+
+        required_threshold: args.opt_value_from_str("--required-threshold")?,
+        presence_comments: args.contains("--presence-comments"),
+        double_option: args.contains("--double-option"),
+
+        string_formats: args.values_from_fn("--format", parse_format)?,
+        dates: args.opt_value_from_fn("--dates", parse_dates)?,
+
+        enum_limit: args.opt_value_from_str("--enum-limit")?,
+        enum_other: args.contains("--enum-unknown"),
+
+        tag_keys: if args.contains("--no-tagged-enums") {
+            vec![]
+        } else {
+            match args.values_from_str("--tag-key")?.as_slice() {
+                [] => vec!["type".into(), "kind".into()],
+                keys => keys.to_vec(),
+            }
+        },
+        external_tags: !args.contains("--no-external-tags"),
 
         field_naming: args
             .opt_value_from_fn(["-f", "--field-naming"], parse_casing)?
-            .unwrap_or_else(|| CasingScheme::Snake),
+            .unwrap_or(CasingScheme::Snake),
 
         struct_naming: args
             .opt_value_from_fn(["-s", "--struct-naming"], parse_casing)?
-            .unwrap_or_else(|| CasingScheme::Pascal),
+            .unwrap_or(CasingScheme::Pascal),
 
         vec_wrapper: args
             .opt_value_from_str::<_, String>("--vec-wrapper")?
@@ -187,19 +566,41 @@ fn parse_args() -> anyhow::Result<json_to_rust::Options> {
             .opt_value_from_str::<_, String>("--map-wrapper")?
             .map(Wrapper::custom_map)
             .unwrap_or_else(Wrapper::std_map),
+
+        placeholder: args.opt_value_from_str("--placeholder")?,
+
+        overrides,
     };
 
-    args.finish()?;
+    let files = args.free()?;
 
-    Ok(opts)
+    Ok((opts, files))
 }
 
 fn main() -> anyhow::Result<()> {
-    let opts = parse_args()?;
+    let (opts, files) = parse_args()?;
 
     let stdin = std::io::stdin();
-    let mut stdin = stdin.lock();
-
     let mut out = std::io::stdout();
-    json_to_rust::generate(opts, &mut stdin, &mut out)
+
+    if files.is_empty() {
+        return json_to_rust::generate(opts, &mut stdin.lock(), &mut out);
+    }
+
+    // stdin can only be read once, and locking it again would never return
+    let stdins = files.iter().filter(|file| *file == "-").count();
+    anyhow::ensure!(stdins <= 1, "'-' (stdin) can only be used once");
+
+    let mut samples = files
+        .iter()
+        .map(|file| -> anyhow::Result<Box<dyn Read>> {
+            if file == "-" {
+                return Ok(Box::new(stdin.lock()));
+            }
+            let fi = File::open(file).with_context(|| format!("cannot open '{}'", file))?;
+            Ok(Box::new(fi))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    json_to_rust::generate_many(opts, samples.iter_mut().map(|s| &mut **s), &mut out)
 }
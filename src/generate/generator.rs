@@ -1,14 +1,16 @@
-use super::item::{Field, Item, Struct};
+use super::item::{Alias, Enum, Field, Item, Struct, Tagging, Variant};
 use crate::{
-    infer::{self, Map, Shape},
-    util, CasingScheme, Options,
+    infer::{self, Map, Presence, Set, Shape, Time},
+    util, CasingScheme, InputMode, Options, Override,
 };
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use util::Wrapper;
 
 #[derive(Debug)]
 pub struct Generator<'a> {
     pub structs: Vec<Struct>,
+    pub enums: Vec<Enum>,
+    pub aliases: Vec<Alias>,
     pub items: Vec<Item>,
     pub opts: &'a Options,
 
@@ -16,17 +18,30 @@ pub struct Generator<'a> {
     pub depth: usize,
 
     pub should_include_map: bool,
+    pub imports: BTreeSet<String>,
+    pub features: BTreeSet<String>,
 
     pub root_at: usize,
+    /// the name of the type made for the root
+    pub root: Option<String>,
     pub wrap_in_vec: Option<Struct>,
+
+    pub path: Vec<String>,
+    pub notes: Vec<String>,
+    pub warnings: Vec<String>,
+    pub parents: Vec<String>,
 }
 
 impl<'a> Generator<'a> {
     pub fn new(opts: &'a Options) -> Self {
-        let (structs, items, seen_structs, depth, wrap_in_vec) = <_>::default();
+        let (structs, enums, items, seen_structs, depth, wrap_in_vec) = <_>::default();
+        let (imports, features, path, notes, warnings, parents, aliases) = <_>::default();
+        let root = None;
 
         Self {
             structs,
+            enums,
+            aliases,
             items,
             opts,
 
@@ -34,13 +49,24 @@ impl<'a> Generator<'a> {
             depth,
 
             should_include_map: false,
+            imports,
+            features,
 
             root_at: 1,
+            root,
             wrap_in_vec,
+
+            path,
+            notes,
+            warnings,
+            parents,
         }
     }
 
     const ANY_VALUE: &'static str = "::serde_json::Value";
+    const DOUBLE_OPTION: &'static str = "::serde_with::rust::double_option";
+    /// variants of a tagged object that share at least this ratio of their keys are one struct
+    const TAG_SIMILARITY: f64 = 0.5;
 
     pub fn walk(&mut self, shape: &Shape, wrap: &Wrapper, name: &str, default: &mut bool) {
         if self.depth == 0
@@ -53,13 +79,18 @@ impl<'a> Generator<'a> {
                 fields: vec![Field {
                     rename: self.opts.json_name.clone(),
                     default: *default,
+                    with: None,
+                    skip_none: false,
+                    comment: None,
+                    flatten: false,
                     binding: "list".into(),
                     kind: self.opts.vec_wrapper.apply(self.opts.root_name.clone()),
                 }],
+                tuple: false,
             });
             assert!(
                 t.is_none(),
-                "only a top-level array should be provided. NDJSON is not supported"
+                "only a single top-level array should be provided"
             );
 
             self.root_at += 1;
@@ -67,41 +98,171 @@ impl<'a> Generator<'a> {
 
         self.depth += 1;
 
+        // an optional node is overridden by its inner shape, so it stays optional
+        let found = self
+            .find_override()
+            .filter(|_| !matches!(shape, Shape::Optional(..)));
+        let name = found.and_then(|o| o.name.as_deref()).unwrap_or(name);
+
+        match (found, shape) {
+            (Some(Override { raw: true, .. }), _) => self.write_primitive(Self::ANY_VALUE, wrap),
+            (Some(Override { kind: Some(ty), .. }), _) => self.write_primitive(ty.as_str(), wrap),
+            (Some(Override { map: true, .. }), Shape::Object(map, _)) => {
+                let ty = Shape::fold(map.values().cloned());
+                self.make_map(&ty, name, wrap, default)
+            }
+            _ => self.walk_shape(shape, wrap, name, default),
+        }
+
+        self.depth -= 1;
+    }
+
+    fn walk_shape(&mut self, shape: &Shape, wrap: &Wrapper, name: &str, default: &mut bool) {
         match shape {
-            Shape::Bottom | Shape::Any | Shape::Null => self.write_primitive(Self::ANY_VALUE, wrap),
+            Shape::Any => self.write_primitive(Self::ANY_VALUE, wrap),
+            Shape::Bottom => self.make_placeholder(name, "was never observed", wrap),
+            Shape::Null => {
+                let wrap = Wrapper::wrap(wrap.clone(), Wrapper::option());
+                self.make_placeholder(name, "was always null", &wrap)
+            }
 
             Shape::Bool => self.write_primitive("bool", wrap),
             Shape::String => self.write_primitive("String", wrap),
             Shape::Integer => self.write_primitive("i64", wrap),
+            Shape::Range(min, max) => {
+                let ty = self.opts.integer_policy.select(*min, *max);
+                self.write_primitive(ty, wrap)
+            }
             Shape::Float => self.write_primitive("f64", wrap),
+            Shape::BigInteger(negative) => {
+                self.require_precision();
+                let ty = self.opts.precision.integer(*negative);
+                self.write_primitive(ty, wrap)
+            }
+            Shape::BigDecimal => {
+                self.require_precision();
+                let ty = self.opts.precision.decimal();
+                self.write_primitive(ty, wrap)
+            }
             Shape::Opaque(ty) => self.write_primitive(ty, wrap),
+            Shape::Time(time) => self.make_time(*time, name, wrap, default),
+            Shape::Recursive => self.make_recursive(wrap),
+            Shape::Named(name, shape) => self.walk_shape(shape, wrap, name, default),
+            Shape::Enum(values, observed, _) => self.make_enum(name, values, *observed, wrap),
+            Shape::Tagged(key, variants) => self.make_tagged(name, key, variants, wrap, default),
+            Shape::Union(members) => self.make_union(name, members, wrap, default),
             Shape::Optional(inner) => {
                 let wrap = Wrapper::wrap(wrap.clone(), Wrapper::option());
                 self.walk(inner, &wrap, name, default)
             }
             Shape::Array(ty) => self.make_vec(ty, name, wrap, default),
-            Shape::Map(ty) => self.make_map(ty, default),
+            Shape::Map(ty) if self.depth == self.root_at => {
+                self.make_root_map(ty, name, wrap, default)
+            }
+            Shape::Map(ty) => self.make_map(ty, name, wrap, default),
 
             Shape::Tuple(els, ..) => {
                 let folded = Shape::fold(els.clone());
                 // eprintln!("folded: [{}; {}]", folded.root(), e);
-                if folded == Shape::Any && els.iter().any(|s| *s != Shape::Any) {
-                    self.make_tuple(els, &Wrapper::default(), default)
+                let heterogeneous = match &folded {
+                    Shape::Optional(ty) => matches!(**ty, Shape::Any | Shape::Union(..)),
+                    ty => matches!(ty, Shape::Any | Shape::Union(..)),
+                };
+                if heterogeneous && els.iter().any(|s| *s != Shape::Any) {
+                    self.make_tuple(name, els, wrap, default)
                 } else {
                     self.make_vec(&folded, name, wrap, default)
                 }
             }
 
-            Shape::Object(ty) => self.make_struct(name, ty, &Wrapper::default(), default),
+            // the root object is always a struct
+            Shape::Object(ty, presence) => match self.external_variants(shape) {
+                Some(variants) if self.depth > 1 => {
+                    self.make_external(name, &variants, wrap, default)
+                }
+                _ => self.make_struct(name, ty, presence, wrap, default),
+            },
         }
+    }
 
-        self.depth -= 1;
+    /// the variants of an object that is an externally tagged enum
+    fn external_variants(&self, shape: &Shape) -> Option<Map> {
+        if !self.opts.external_tags {
+            return None;
+        }
+        shape
+            .external_variants()
+            .filter(|variants| variants.keys().all(|key| is_identifier(key)))
+    }
+
+    /// the json path of the current node, e.g. `$.data.items[].created_at`
+    fn json_path(&self) -> String {
+        util::json_path(&self.path)
     }
 
-    fn make_tuple(&mut self, shapes: &[Shape], wrap: &Wrapper, default: &mut bool) {
+    fn find_override(&self) -> Option<&'a Override> {
+        let opts: &'a Options = self.opts;
+        let path = self.json_path();
+        opts.overrides.iter().find(|o| o.matches(&path))
+    }
+
+    /// a type for a value that was never seen, e.g. the items of an empty array
+    fn make_placeholder(&mut self, name: &str, reason: &str, wrap: &Wrapper) {
+        let ty = match &self.opts.placeholder {
+            Some(ty) => ty.clone(),
+            None if name.is_empty() => Self::ANY_VALUE.to_string(),
+            None => {
+                // the items of an array are named after the array, e.g. 'TagsItem',
+                // except for the items of a top-level array which are the root
+                let name = match self.path.last() {
+                    Some(segment) if segment == "[]" && self.depth != self.root_at => {
+                        format!("{} item", name)
+                    }
+                    _ => name.to_string(),
+                };
+                let name = util::fix_name(&name, &mut self.seen_structs, self.opts.struct_naming);
+                self.aliases.push(Alias {
+                    name: name.clone(),
+                    kind: Self::ANY_VALUE.to_string(),
+                    comment: Some(format!("TODO: {}", reason)),
+                });
+                self.set_root(&name);
+                name
+            }
+        };
+
+        self.warnings.push(format!(
+            "'{}' {}, using {}. a sample with a value for it would give a better type",
+            self.json_path(),
+            reason,
+            ty
+        ));
+        self.write_primitive(ty, wrap)
+    }
+
+    fn make_tuple(
+        &mut self,
+        input_name: &str,
+        shapes: &[Shape],
+        wrap: &Wrapper,
+        default: &mut bool,
+    ) {
+        if !input_name.is_empty() {
+            return self.make_tuple_struct(input_name, shapes, wrap);
+        }
+
         let (mut types, mut defs) = (String::new(), Vec::new());
         for shape in shapes {
-            self.walk(shape, wrap, "", default);
+            // a tuple can't use a serde adapter for its elements
+            let shape = match shape {
+                Shape::Time(time) => time.fallback(),
+                Shape::Optional(ty) => match **ty {
+                    Shape::Time(time) => Shape::Optional(Box::new(time.fallback())),
+                    _ => shape.clone(),
+                },
+                _ => shape.clone(),
+            };
+            self.walk(&shape, &Wrapper::default(), "", default);
             if !types.is_empty() {
                 types.push_str(", ");
             }
@@ -118,12 +279,67 @@ impl<'a> Generator<'a> {
         }
 
         self.items.push(Item {
-            ident: Wrapper::tuple().apply(types),
+            ident: wrap.apply(Wrapper::tuple().apply(types)),
+            body: defs,
+            with: None,
+        });
+    }
+
+    fn make_tuple_struct(&mut self, input_name: &str, shapes: &[Shape], wrap: &Wrapper) {
+        let struct_name =
+            util::fix_name(input_name, &mut self.seen_structs, self.opts.struct_naming);
+
+        // the trailing optional elements may be missing from the array
+        let required = shapes
+            .iter()
+            .rposition(|shape| !matches!(shape, Shape::Optional(..)))
+            .map_or(0, |i| i + 1);
+
+        let (mut fields, mut defs) = (Vec::new(), Vec::new());
+        for (i, shape) in shapes.iter().enumerate() {
+            let mut field_default = false;
+
+            let name = format!("{} {}", input_name, i);
+            self.path.push(format!("[{}]", i));
+            self.walk(shape, &Wrapper::default(), &name, &mut field_default);
+            self.path.pop();
+            let item = self.items.pop().unwrap();
+            defs.extend(item.body);
+
+            fields.push(Field {
+                rename: None,
+                default: field_default || i >= required,
+                with: item.with,
+                skip_none: false,
+                comment: None,
+                flatten: false,
+                binding: i.to_string(),
+                kind: item.ident,
+            });
+        }
+
+        let struct_name = self.push_struct(Struct {
+            rename: None,
+            name: struct_name,
+            fields,
+            tuple: true,
+        });
+
+        self.items.push(Item {
+            ident: wrap.apply(struct_name),
             body: defs,
+            with: None,
         });
     }
 
-    fn make_struct(&mut self, input_name: &str, map: &Map, wrap: &Wrapper, default: &mut bool) {
+    fn make_struct(
+        &mut self,
+        input_name: &str,
+        map: &Map,
+        presence: &Presence,
+        wrap: &Wrapper,
+        default: &mut bool,
+    ) {
         let struct_naming = if self.depth == 1 {
             CasingScheme::Identity
         } else {
@@ -131,12 +347,22 @@ impl<'a> Generator<'a> {
         };
 
         let struct_name = util::fix_name(input_name, &mut self.seen_structs, struct_naming);
+        self.parents.push(struct_name.clone());
 
         let mut defs = Vec::new();
         let mut body = Vec::new();
 
         let mut seen_fields = HashSet::new();
 
+        let merged;
+        let map = match self.opts.merge_similar {
+            Some(threshold) => {
+                merged = self.merge_similar(map, threshold);
+                &merged
+            }
+            None => map,
+        };
+
         fn collapse_option_vec(shape: &Shape, should_collapse: bool) -> Option<&Shape> {
             if should_collapse {
                 if let Shape::Optional(ty) = shape {
@@ -149,38 +375,83 @@ impl<'a> Generator<'a> {
         }
 
         for (name, shape) in map.iter().rev() {
-            let field_name = util::fix_name(name, &mut seen_fields, self.opts.field_naming);
+            self.path.push(name.clone());
+            let field_name = match self.find_override().and_then(|o| o.rename.as_ref()) {
+                Some(rename) => util::fix_name(rename, &mut seen_fields, CasingScheme::Identity),
+                None => util::fix_name(name, &mut seen_fields, self.opts.field_naming),
+            };
             let field_renamed = field_name != *name;
 
-            match shape {
-                Shape::Object(map) => {
-                    // if the type is uniform and unfolds to the same type
-                    // or if the type isn't uniform and won't unfold to the same type
-                    // then we'll keep walking the tree
-                    //
-                    // otherwise, just use the shorthand for 'complex' objects
-                    match (shape.is_uniform(), shape.unfold().is_some()) {
-                        (false, false) | (true, true) => {
-                            self.walk(shape, &Wrapper::default(), name, default)
-                        }
-                        _ => self.make_field_map(map),
-                    }
+            let (present, null) = presence.rates(name);
+            let shape = match shape {
+                // the field was never null, and missing from few enough objects to be required
+                Shape::Optional(ty)
+                    if null == 0.0
+                        && matches!(self.opts.required_threshold, Some(t) if present >= t) =>
+                {
+                    self.notes.push(format!(
+                        "{} is required but was missing from {:.1}% of objects",
+                        self.json_path(),
+                        (1.0 - present) * 100.0
+                    ));
+                    &**ty
                 }
-                Shape::Map(_) => unreachable!("shouldn't have a map here"),
+                shape => shape,
+            };
+
+            // the field was both missing and null, so `None` and `Some(None)` are kept apart
+            let double_option = self.opts.double_option
+                && present < 1.0
+                && null > 0.0
+                && matches!(shape, Shape::Optional(..));
+            let doubled;
+            let shape = if double_option {
+                doubled = Shape::Optional(Box::new(shape.clone()));
+                &doubled
+            } else {
+                shape
+            };
+
+            let comment = Some(presence)
+                .filter(|presence| self.opts.presence_comments && presence.seen > 0)
+                .map(|presence| {
+                    let mut comment = format!(
+                        "present in {:.1}% of {} objects",
+                        present * 100.0,
+                        presence.seen
+                    );
+                    if null > 0.0 {
+                        comment.push_str(&format!(", null in {:.1}%", null * 100.0));
+                    }
+                    comment
+                });
+
+            match shape {
+                Shape::Object(..) => self.walk(shape, &Wrapper::default(), name, default),
                 _ => {
                     if let Some(shape) = collapse_option_vec(shape, self.opts.collapse_option_vec) {
                         // mark it as default
                         *default = true;
-                        self.walk(shape, wrap, &field_name, default)
+                        self.walk(shape, &Wrapper::default(), &field_name, default)
                     } else {
-                        self.walk(shape, wrap, &field_name, default)
+                        self.walk(shape, &Wrapper::default(), &field_name, default)
                     }
                 }
             }
 
+            self.path.pop();
             let item = self.items.pop().unwrap();
             defs.extend(item.body);
 
+            let with = if double_option {
+                // a missing field has to default to `None` rather than `Some(None)`,
+                // and `None` has to stay missing rather than becoming null
+                *default = true;
+                Some(Self::DOUBLE_OPTION.to_string())
+            } else {
+                item.with
+            };
+
             body.push(Field {
                 rename: if field_renamed {
                     Some(name.clone())
@@ -188,56 +459,744 @@ impl<'a> Generator<'a> {
                     None
                 },
                 default: *default,
+                with,
+                skip_none: double_option,
+                comment,
+                flatten: false,
                 binding: field_name,
                 kind: item.ident,
             });
             *default = false;
         }
 
-        self.structs.push(Struct {
+        self.parents.pop();
+        let struct_name = self.push_struct(Struct {
             rename: self
                 .opts
                 .json_name
                 .as_ref()
                 .filter(|_| self.wrap_in_vec.is_none() && self.depth == self.root_at)
-                .map(Clone::clone),
-            name: struct_name.clone(),
+                .cloned(),
+            name: struct_name,
             fields: body,
+            tuple: false,
         });
 
         self.items.push(Item {
-            ident: struct_name,
+            ident: wrap.apply(struct_name),
             body: defs,
+            with: None,
         });
     }
 
-    fn make_field_map(&mut self, map: &Map) {
-        let shape = infer::Shape::fold(map.values().cloned());
-        let local = infer::Local::new(shape);
+    fn make_enum(&mut self, input_name: &str, values: &Set, observed: u64, wrap: &Wrapper) {
+        // only use an enum if there are a few values and they were seen more than once.
+        // otherwise its probably just a string. a schema declares its enums
+        let limit = self.opts.enum_limit.unwrap_or_default();
+        let declared = self.opts.input_mode == InputMode::Schema;
+        if input_name.is_empty()
+            || !(declared || values.len() <= limit && observed > values.len() as u64)
+            || !values.iter().all(|value| is_identifier(value))
+        {
+            return self.write_primitive("String", wrap);
+        }
+
+        let enum_name = util::fix_name(input_name, &mut self.seen_structs, self.opts.struct_naming);
 
-        let mut ident = String::new();
-        local.format(&mut ident, &self.opts);
-        let ident = self.opts.map_wrapper.apply(ident);
-        self.should_include_map = true;
+        let mut seen_variants = HashSet::new();
+        let mut variants = values
+            .iter()
+            .map(|value| {
+                let name = util::fix_name(value, &mut seen_variants, CasingScheme::Pascal);
+                Variant {
+                    rename: Some(value.clone()).filter(|value| *value != name),
+                    other: false,
+                    name,
+                    kind: None,
+                    with: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if self.opts.enum_other {
+            variants.push(Variant {
+                rename: None,
+                other: true,
+                name: util::fix_name("Unknown", &mut seen_variants, CasingScheme::Pascal),
+                kind: None,
+                with: None,
+            });
+        }
+
+        let enum_name = self.push_enum(Enum {
+            name: enum_name,
+            tagging: Tagging::External,
+            variants,
+        });
+
+        self.items.push(Item {
+            ident: wrap.apply(enum_name),
+            body: vec![],
+            with: None,
+        });
+    }
+
+    fn make_tagged(
+        &mut self,
+        input_name: &str,
+        key: &str,
+        variants: &Map,
+        wrap: &Wrapper,
+        default: &mut bool,
+    ) {
+        // the tag only predicts the fields if no two variants mostly have the same fields
+        let objects = variants
+            .values()
+            .map(|shape| match shape {
+                Shape::Object(map, _) => map.clone(),
+                _ => Map::default(),
+            })
+            .collect::<Vec<_>>();
+        let dissimilar = objects.iter().enumerate().all(|(i, left)| {
+            objects[i + 1..]
+                .iter()
+                .all(|right| infer::similarity(left, right) < Self::TAG_SIMILARITY)
+        });
+
+        if input_name.is_empty()
+            || variants.len() < 2
+            || !dissimilar
+            || !variants.keys().all(|value| is_identifier(value))
+        {
+            let shape = Shape::untag(key.to_string(), variants.clone());
+            return self.walk_shape(&shape, wrap, input_name, default);
+        }
+
+        let enum_name = util::fix_name(input_name, &mut self.seen_structs, self.opts.struct_naming);
+
+        let mut seen_variants = HashSet::new();
+        let mut body = Vec::new();
+
+        for (value, shape) in variants {
+            let name = util::fix_name(value, &mut seen_variants, CasingScheme::Pascal);
+
+            self.walk(shape, &Wrapper::default(), value, default);
+            let item = self.items.pop().unwrap();
+
+            body.push(Variant {
+                rename: Some(value.clone()).filter(|value| *value != name),
+                other: false,
+                name,
+                kind: Some(item.ident),
+                with: None,
+            });
+        }
+
+        let enum_name = self.push_enum(Enum {
+            name: enum_name,
+            tagging: Tagging::Internal(key.to_string()),
+            variants: body,
+        });
+
+        self.items.push(Item {
+            ident: wrap.apply(enum_name),
+            body: vec![],
+            with: None,
+        });
+    }
+
+    fn make_external(
+        &mut self,
+        input_name: &str,
+        variants: &Map,
+        wrap: &Wrapper,
+        default: &mut bool,
+    ) {
+        if input_name.is_empty() {
+            return self.write_primitive(Self::ANY_VALUE, wrap);
+        }
+
+        let enum_name = util::fix_name(input_name, &mut self.seen_structs, self.opts.struct_naming);
+
+        let mut seen_variants = HashSet::new();
+        let mut body = Vec::new();
+
+        for (key, shape) in variants {
+            let name = util::fix_name(key, &mut seen_variants, CasingScheme::Pascal);
+
+            self.path.push(key.clone());
+            self.walk(shape, &Wrapper::default(), key, default);
+            self.path.pop();
+            let item = self.items.pop().unwrap();
+
+            body.push(Variant {
+                rename: Some(key.clone()).filter(|key| *key != name),
+                other: false,
+                name,
+                kind: Some(item.ident),
+                with: item.with,
+            });
+        }
+
+        let enum_name = self.push_enum(Enum {
+            name: enum_name,
+            tagging: Tagging::External,
+            variants: body,
+        });
+
+        self.items.push(Item {
+            ident: wrap.apply(enum_name),
+            body: vec![],
+            with: None,
+        });
+    }
+
+    fn make_union(
+        &mut self,
+        input_name: &str,
+        members: &[Shape],
+        wrap: &Wrapper,
+        default: &mut bool,
+    ) {
+        if input_name.is_empty() {
+            return self.write_primitive(Self::ANY_VALUE, wrap);
+        }
+
+        let enum_name = util::fix_name(input_name, &mut self.seen_structs, self.opts.struct_naming);
+
+        // several objects are named after a key that only they have, e.g. 'Url' for `{"url": ..}`
+        let objects = members
+            .iter()
+            .map(|shape| match shape {
+                Shape::Named(..) => None,
+                shape => shape.fields(),
+            })
+            .collect::<Vec<_>>();
+        let distinct = |i: usize| {
+            let fields = objects[i]
+                .as_ref()
+                .filter(|_| objects.iter().flatten().count() > 1)?;
+            fields.keys().find(|key| {
+                is_identifier(key)
+                    && objects.iter().enumerate().all(|(j, other)| {
+                        i == j || !matches!(other, Some(other) if other.contains_key(*key))
+                    })
+            })
+        };
+
+        let mut seen_variants = HashSet::new();
+        let mut body = Vec::new();
+
+        for (i, shape) in members.iter().enumerate() {
+            let variant = match distinct(i) {
+                Some(key) => key.as_str(),
+                None => variant_name(shape),
+            };
+            let name = util::fix_name(variant, &mut seen_variants, CasingScheme::Pascal);
+
+            // nested types are named after the enum, e.g. 'ValueObject'
+            let nested = format!("{} {}", input_name, name);
+            self.walk(shape, &Wrapper::default(), &nested, default);
+            let item = self.items.pop().unwrap();
+
+            body.push(Variant {
+                rename: None,
+                other: false,
+                name,
+                kind: Some(item.ident),
+                with: item.with,
+            });
+        }
+
+        let enum_name = self.push_enum(Enum {
+            name: enum_name,
+            tagging: Tagging::Untagged,
+            variants: body,
+        });
+
+        self.items.push(Item {
+            ident: wrap.apply(enum_name),
+            body: vec![],
+            with: None,
+        });
+    }
+
+    /// unifies sibling objects whose keys are similar enough, so they'll become a single struct
+    fn merge_similar(&mut self, map: &Map, threshold: f64) -> Map {
+        let mut map = map.clone();
+        let objects = map
+            .iter()
+            .filter(|(_, shape)| matches!(shape, Shape::Object(fields, _) if !fields.is_empty()))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        let mut grouped = HashSet::new();
+        for (i, name) in objects.iter().enumerate() {
+            if !grouped.insert(name) {
+                continue;
+            }
+
+            let (mut unified, mut group) = (map[name].clone(), vec![name]);
+            for other in &objects[i + 1..] {
+                let similar = match (&unified, &map[other]) {
+                    (Shape::Object(left, _), Shape::Object(right, _)) => {
+                        infer::similarity(left, right) >= threshold
+                    }
+                    _ => false,
+                };
+                if !similar || grouped.contains(other) {
+                    continue;
+                }
+
+                unified = Shape::factor(unified, map[other].clone());
+                grouped.insert(other);
+                group.push(other);
+            }
+
+            if group.len() < 2 {
+                continue;
+            }
+
+            let mut paths = Vec::new();
+            for name in group {
+                map[name] = unified.clone();
+
+                self.path.push(name.clone());
+                paths.push(self.json_path());
+                self.path.pop();
+            }
+            self.notes.push(format!(
+                "merged similar objects into one struct: {}",
+                paths.join(", ")
+            ));
+        }
+
+        map
+    }
+
+    /// replaces structs that only differ in the type of a single field with a generic struct,
+    /// e.g. `Page<T>`, which is then used with the type of that field
+    pub fn extract_generics(&mut self) {
+        // the field that the structs differ in, if they only differ in the type of one field
+        fn differs_in(left: &Struct, right: &Struct) -> Option<String> {
+            fn sorted(s: &Struct) -> Vec<&Field> {
+                let mut fields = s.fields.iter().collect::<Vec<_>>();
+                fields.sort_by(|l, r| l.binding.cmp(&r.binding));
+                fields
+            }
+
+            if left.fields.len() != right.fields.len() {
+                return None;
+            }
+
+            let mut differs = None;
+            for (l, r) in sorted(left).into_iter().zip(sorted(right)) {
+                if (
+                    &l.rename,
+                    l.default,
+                    &l.with,
+                    l.skip_none,
+                    l.flatten,
+                    &l.binding,
+                ) != (
+                    &r.rename,
+                    r.default,
+                    &r.with,
+                    r.skip_none,
+                    r.flatten,
+                    &r.binding,
+                ) {
+                    return None;
+                }
+                if l.kind != r.kind && differs.replace(l.binding.clone()).is_some() {
+                    return None;
+                }
+            }
+            differs
+        }
+
+        loop {
+            // the root has to keep its name
+            let root = self.root.as_deref();
+            let candidate = |(_, s): &(usize, &Struct)| {
+                Some(s.name.as_str()) != root
+                    && !s.tuple
+                    && s.rename.is_none()
+                    && !s.name.contains('<')
+            };
+
+            let group = self
+                .structs
+                .iter()
+                .enumerate()
+                .filter(candidate)
+                .find_map(|(i, left)| {
+                    let mut group = vec![i];
+                    let mut binding = None;
+                    for (j, right) in self.structs.iter().enumerate().skip(i + 1) {
+                        if !candidate(&(j, right)) {
+                            continue;
+                        }
+                        match differs_in(left, right) {
+                            Some(field)
+                                if binding.get_or_insert_with(|| field.clone()) == &field =>
+                            {
+                                group.push(j)
+                            }
+                            _ => {}
+                        }
+                    }
+                    binding
+                        .filter(|_| group.len() > 1)
+                        .map(|binding| (group, binding))
+                });
+
+            let (group, binding) = match group {
+                Some(group) => group,
+                None => break,
+            };
+
+            let kinds = group
+                .iter()
+                .map(|&i| {
+                    let s = &self.structs[i];
+                    let field = s.fields.iter().find(|f| f.binding == binding).unwrap();
+                    (s.name.clone(), field.kind.clone())
+                })
+                .collect::<Vec<_>>();
+
+            // only the part of the type that differs is generic, e.g. `Vec<T>`
+            let (prefix, suffix) = common_affixes(kinds.iter().map(|(_, kind)| kind.as_str()));
+            let (prefix, suffix) = (prefix.to_string(), suffix.to_string());
+            let param = "T";
+
+            let mut generic = self.structs.remove(group[0]);
+            for &i in group[1..].iter().rev() {
+                let s = self.structs.remove(i - 1);
+                self.seen_structs.remove(&s.name);
+            }
+            self.seen_structs.remove(&generic.name);
+
+            // named after what the structs have in common, or after the first one
+            let names = kinds
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            let name = match common_words(&names) {
+                common if common.is_empty() => generic.name.clone(),
+                common => common,
+            };
+            let name = util::fix_name(&name, &mut self.seen_structs, CasingScheme::Identity);
+
+            generic.name = format!("{}<{}>", name, param);
+            for field in &mut generic.fields {
+                if field.binding == binding {
+                    field.kind = format!("{}{}{}", prefix, param, suffix);
+                }
+            }
+            self.structs.insert(group[0], generic);
+
+            for (old, kind) in kinds {
+                let ty = &kind[prefix.len()..kind.len() - suffix.len()];
+                let new = format!("{}<{}>", name, ty);
+                self.rename_type(&old, &new);
+            }
+        }
+    }
+
+    /// replaces every use of a type with another type
+    fn rename_type(&mut self, old: &str, new: &str) {
+        let fields = self
+            .structs
+            .iter_mut()
+            .chain(self.wrap_in_vec.as_mut())
+            .flat_map(|s| s.fields.iter_mut().map(|f| &mut f.kind));
+        let variants = self
+            .enums
+            .iter_mut()
+            .flat_map(|e| e.variants.iter_mut().filter_map(|v| v.kind.as_mut()));
+
+        for kind in fields.chain(variants).chain(self.root.as_mut()) {
+            *kind = replace_type(kind, old, new);
+        }
+    }
+
+    /// moves the fields that several structs have in common into a struct of their own,
+    /// which is then flattened into each of them
+    pub fn flatten_shared(&mut self, min_fields: usize) {
+        fn same(left: &Field, right: &Field) -> bool {
+            !left.flatten
+                && (
+                    &left.rename,
+                    left.default,
+                    &left.with,
+                    left.skip_none,
+                    &left.binding,
+                    &left.kind,
+                ) == (
+                    &right.rename,
+                    right.default,
+                    &right.with,
+                    right.skip_none,
+                    &right.binding,
+                    &right.kind,
+                )
+        }
+
+        let min_fields = min_fields.max(1);
+        loop {
+            let structs = &self.structs;
+            let has_all = |s: &Struct, fields: &[Field]| {
+                !s.tuple && fields.iter().all(|f| s.fields.iter().any(|g| same(f, g)))
+            };
+
+            // the group that removes the most fields
+            let mut best: Option<(Vec<Field>, Vec<usize>)> = None;
+            for (i, left) in structs.iter().enumerate().filter(|(_, s)| !s.tuple) {
+                for right in structs[i + 1..].iter().filter(|s| !s.tuple) {
+                    let common = left
+                        .fields
+                        .iter()
+                        .filter(|f| right.fields.iter().any(|g| same(f, g)))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if common.len() < min_fields {
+                        continue;
+                    }
+
+                    let owners = (0..structs.len())
+                        .filter(|&k| has_all(&structs[k], &common))
+                        .collect::<Vec<_>>();
+
+                    let score =
+                        |(fields, owners): &(Vec<Field>, Vec<usize>)| fields.len() * owners.len();
+                    let candidate = (common, owners);
+                    let better = match &best {
+                        Some(best) => score(&candidate) > score(best),
+                        None => true,
+                    };
+                    if better {
+                        best = Some(candidate);
+                    }
+                }
+            }
+
+            let (common, owners) = match best {
+                Some(best) => best,
+                None => break,
+            };
+
+            let name = util::fix_name("Shared", &mut self.seen_structs, self.opts.struct_naming);
+            for owner in owners {
+                let owner = &mut self.structs[owner];
+                owner.fields.retain(|f| !common.iter().any(|c| same(c, f)));
+
+                let mut seen = owner.fields.iter().map(|f| f.binding.clone()).collect();
+                owner.fields.push(Field {
+                    rename: None,
+                    default: false,
+                    with: None,
+                    skip_none: false,
+                    comment: None,
+                    flatten: true,
+                    binding: util::fix_name(&name, &mut seen, self.opts.field_naming),
+                    kind: name.clone(),
+                });
+            }
+
+            // the root has to stay the last struct
+            let fields = common
+                .into_iter()
+                .map(|field| Field {
+                    comment: None,
+                    ..field
+                })
+                .collect();
+            self.structs.insert(
+                0,
+                Struct {
+                    rename: None,
+                    name,
+                    fields,
+                    tuple: false,
+                },
+            );
+        }
+    }
+
+    /// reuses a struct with the same fields if one was already made, returning its name
+    fn push_struct(&mut self, item: Struct) -> String {
+        // a recursive struct refers to itself, so its own name doesn't make it different
+        let same_fields = |existing: &Struct| {
+            existing.fields.len() == item.fields.len()
+                && existing
+                    .fields
+                    .iter()
+                    .zip(&item.fields)
+                    .all(|(left, right)| {
+                        let kind = replace_type(&right.kind, &item.name, &existing.name);
+                        *left
+                            == Field {
+                                kind,
+                                ..right.clone()
+                            }
+                    })
+        };
+
+        // the root has to be the last struct
+        let existing = self
+            .structs
+            .iter()
+            .filter(|_| self.depth != self.root_at)
+            .find(|s| s.rename == item.rename && same_fields(s));
+
+        let name = match existing {
+            Some(existing) => {
+                // free up the name so it can be used by another type
+                self.seen_structs.remove(&item.name);
+                existing.name.clone()
+            }
+            None => {
+                let name = item.name.clone();
+                self.structs.push(item);
+                name
+            }
+        };
+        self.set_root(&name);
+        name
+    }
+
+    /// reuses an enum with the same variants if one was already made, returning its name
+    fn push_enum(&mut self, item: Enum) -> String {
+        let existing = self
+            .enums
+            .iter()
+            .find(|e| e.tagging == item.tagging && e.variants == item.variants);
+
+        let name = match existing {
+            Some(existing) => {
+                self.seen_structs.remove(&item.name);
+                existing.name.clone()
+            }
+            None => {
+                let name = item.name.clone();
+                self.enums.push(item);
+                name
+            }
+        };
+        self.set_root(&name);
+        name
+    }
+
+    fn set_root(&mut self, name: &str) {
+        if self.depth == self.root_at {
+            self.root = Some(name.to_string());
+        }
+    }
+
+    fn make_recursive(&mut self, wrap: &Wrapper) {
+        let name = match self.parents.last() {
+            Some(name) => name.clone(),
+            None => return self.write_primitive(Self::ANY_VALUE, wrap),
+        };
+
+        // a collection already provides the indirection, otherwise it has to be boxed
+        let boxed = wrap
+            .apply(String::new())
+            .replace("Option<", "")
+            .replace('>', "")
+            .is_empty();
+
+        if boxed {
+            self.write_primitive(format!("Box<{}>", name), wrap)
+        } else {
+            self.write_primitive(name, wrap)
+        }
+    }
+
+    fn make_time(&mut self, time: Time, name: &str, wrap: &Wrapper, default: &mut bool) {
+        let library = match self.opts.dates {
+            Some(library) => library,
+            None => return self.walk(&time.fallback(), wrap, name, default),
+        };
+
+        // the serde adapters only work on the field itself or on an Option
+        let optional = match &*wrap.apply(String::new()) {
+            "" => false,
+            "Option<>" => true,
+            _ => match library.select(time, false) {
+                (_, Some(..)) => return self.walk(&time.fallback(), wrap, name, default),
+                (ty, None) => {
+                    self.features.insert(library.feature().to_string());
+                    return self.write_primitive(ty, wrap);
+                }
+            },
+        };
+
+        let (ty, with) = library.select(time, optional);
+        if with.is_some() && optional {
+            // the adapters for options don't default to None when the field is missing
+            *default = true;
+        }
 
+        self.features.insert(library.feature().to_string());
         self.items.push(Item {
-            ident,
+            ident: wrap.apply(ty.to_string()),
             body: vec![],
-        })
+            with: with.map(ToString::to_string),
+        });
     }
 
-    fn make_map(&mut self, ty: &Shape, default: &mut bool) {
-        self.walk(ty, &self.opts.map_wrapper, "", default);
+    /// the root has to be a type of its own, so a map gets an alias, e.g. `Root = HashMap<..>`
+    fn make_root_map(&mut self, ty: &Shape, name: &str, wrap: &Wrapper, default: &mut bool) {
+        let alias = util::fix_name(name, &mut self.seen_structs, self.opts.struct_naming);
+        self.make_map(ty, &format!("{} value", name), &Wrapper::default(), default);
+
+        let item = self.items.pop().unwrap();
+        self.aliases.push(Alias {
+            name: alias.clone(),
+            kind: item.ident,
+            comment: None,
+        });
+        self.set_root(&alias);
+
+        self.items.push(Item {
+            ident: wrap.apply(alias),
+            body: item.body,
+            with: None,
+        });
+    }
+
+    // the values go through the walk like any other node, so objects become structs
+    fn make_map(&mut self, ty: &Shape, name: &str, wrap: &Wrapper, default: &mut bool) {
+        self.path.push("[*]".into());
+        self.walk(
+            ty,
+            &wrap.clone().wrap(self.opts.map_wrapper.clone()),
+            name,
+            default,
+        );
+        self.path.pop();
         self.should_include_map = true;
     }
 
     fn make_vec(&mut self, ty: &Shape, name: &str, wrap: &Wrapper, default: &mut bool) {
+        self.path.push("[]".into());
         self.walk(
             ty,
             &wrap.clone().wrap(self.opts.vec_wrapper.clone()),
             name,
             default,
         );
+        self.path.pop();
+    }
+
+    fn require_precision(&mut self) {
+        let precision = self.opts.precision;
+        self.imports
+            .extend(precision.import().map(ToString::to_string));
+        self.features
+            .extend(precision.feature().map(ToString::to_string));
     }
 
     fn write_primitive(&mut self, s: impl Into<String>, wrap: &Wrapper) {
@@ -245,6 +1204,809 @@ impl<'a> Generator<'a> {
         self.items.push(Item {
             ident: wrap.apply(s),
             body: vec![],
+            with: None,
         });
     }
 }
+
+/// the name of the variant of an untagged enum for a shape
+fn variant_name(shape: &Shape) -> &str {
+    match shape {
+        Shape::Named(name, _) => name,
+        Shape::Bool => "Bool",
+        Shape::Integer
+        | Shape::Range(..)
+        | Shape::Float
+        | Shape::BigInteger(..)
+        | Shape::BigDecimal
+        | Shape::Time(Time::Seconds)
+        | Shape::Time(Time::Milliseconds) => "Number",
+        Shape::Time(..) => "Time",
+        Shape::String | Shape::Enum(..) | Shape::Opaque(..) => "Text",
+        Shape::Array(..) | Shape::Tuple(..) => "List",
+        Shape::Map(..) => "Map",
+        Shape::Object(..) | Shape::Tagged(..) | Shape::Recursive => "Object",
+        _ => "Other",
+    }
+}
+
+/// the longest prefix and suffix that all of the types have, without splitting a name
+fn common_affixes<'t>(mut kinds: impl Iterator<Item = &'t str> + Clone) -> (&'t str, &'t str) {
+    let first = kinds.next().unwrap_or_default();
+    let is_boundary = |c: char| !(c.is_alphanumeric() || c == '_' || c == ':');
+
+    let prefix = kinds.clone().fold(first.len(), |len, kind| {
+        first
+            .chars()
+            .zip(kind.chars())
+            .take(len)
+            .take_while(|(l, r)| l == r)
+            .count()
+    });
+    // back off to the last boundary, so `Vec<UserItem>` and `Vec<PostItem>` give `Vec<`
+    let prefix = first[..prefix].rfind(is_boundary).map_or(0, |i| i + 1);
+
+    let suffix = kinds.fold(first.len() - prefix, |len, kind| {
+        first
+            .chars()
+            .rev()
+            .zip(kind.chars().rev())
+            .take(len)
+            .take_while(|(l, r)| l == r)
+            .count()
+    });
+    let tail = &first[first.len() - suffix..];
+    let suffix = tail.find(is_boundary).map_or(0, |i| tail.len() - i);
+
+    (&first[..prefix], &first[first.len() - suffix..])
+}
+
+/// the leading and trailing words that the names share, e.g. `UserPage` and `PostPage` give `Page`
+fn common_words(names: &[&str]) -> String {
+    fn words(name: &str) -> Vec<&str> {
+        let mut words = Vec::new();
+        let mut start = 0;
+        for (i, c) in name.char_indices().skip(1) {
+            if c.is_uppercase() {
+                words.push(&name[start..i]);
+                start = i;
+            }
+        }
+        words.push(&name[start..]);
+        words
+    }
+
+    let mut names = names.iter().map(|name| words(name));
+    let first = match names.next() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+
+    let (prefix, suffix) = names.fold((first.len(), first.len()), |(prefix, suffix), other| {
+        let same = |(l, r): &(&&str, &&str)| l == r;
+        let prefix = first
+            .iter()
+            .zip(&other)
+            .take(prefix)
+            .take_while(same)
+            .count();
+        let suffix = first
+            .iter()
+            .rev()
+            .zip(other.iter().rev())
+            .take(suffix)
+            .take_while(same)
+            .count();
+        (prefix, suffix)
+    });
+
+    // the names are all the same
+    if prefix == first.len() {
+        return first.concat();
+    }
+    let suffix = suffix.min(first.len() - prefix);
+    first[..prefix].concat() + &first[first.len() - suffix..].concat()
+}
+
+/// replaces the type name `old` in `kind`, but not inside of other names
+fn replace_type(kind: &str, old: &str, new: &str) -> String {
+    let mut out = String::with_capacity(kind.len());
+    let mut rest = kind;
+    while !rest.is_empty() {
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(rest.len());
+
+        match &rest[..len] {
+            "" => {
+                let c = rest.chars().next().unwrap();
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+            ident if ident == old => out.push_str(new),
+            ident => out.push_str(ident),
+        }
+        rest = &rest[len..];
+    }
+    out
+}
+
+fn is_identifier(value: &str) -> bool {
+    let name = CasingScheme::Pascal.convert(value);
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{generate, options},
+        IntegerPolicy,
+    };
+
+    fn field(binding: &str, kind: &str) -> Field {
+        Field {
+            rename: None,
+            default: false,
+            with: None,
+            skip_none: false,
+            comment: None,
+            flatten: false,
+            binding: binding.into(),
+            kind: kind.into(),
+        }
+    }
+
+    fn named(name: &str, fields: Vec<Field>) -> Struct {
+        Struct {
+            rename: None,
+            name: name.into(),
+            fields,
+            tuple: false,
+        }
+    }
+
+    #[test]
+    fn reuse_types() {
+        let opts = options();
+        let mut g = Generator::new(&opts);
+        g.depth = 2;
+
+        g.seen_structs.insert("B".into());
+        assert_eq!(g.push_struct(named("B", vec![field("x", "i64")])), "B");
+
+        // the same fields reuse the struct, and free up the name
+        g.seen_structs.insert("A".into());
+        assert_eq!(g.push_struct(named("A", vec![field("x", "i64")])), "B");
+        assert!(!g.seen_structs.contains("A"));
+        assert_eq!(g.push_struct(named("C", vec![field("y", "i64")])), "C");
+        assert_eq!(g.structs.len(), 2);
+
+        // the root always gets its own struct
+        g.depth = g.root_at;
+        assert_eq!(
+            g.push_struct(named("Root", vec![field("x", "i64")])),
+            "Root"
+        );
+        assert_eq!(g.root.as_deref(), Some("Root"));
+        assert_eq!(g.structs.len(), 3);
+
+        let variant = |name: &str| Variant {
+            rename: None,
+            other: false,
+            name: name.into(),
+            kind: None,
+            with: None,
+        };
+        let enumeration = |name: &str, tagging| Enum {
+            name: name.into(),
+            tagging,
+            variants: vec![variant("On"), variant("Off")],
+        };
+
+        g.depth = 2;
+        g.seen_structs.insert("E".into());
+        g.seen_structs.insert("F".into());
+        assert_eq!(g.push_enum(enumeration("E", Tagging::External)), "E");
+        assert_eq!(g.push_enum(enumeration("F", Tagging::External)), "E");
+        assert!(!g.seen_structs.contains("F"));
+        assert_eq!(g.push_enum(enumeration("G", Tagging::Untagged)), "G");
+        assert_eq!(g.enums.len(), 2);
+
+        let out = generate(
+            r#"{"a":{"x":1,"y":"s"},"b":{"x":2,"y":"t"},"c":{"b":{"z":true,"w":"u"},"d":1}}"#,
+            options(),
+        );
+        assert!(out.contains("pub a: B2,\n    pub b: B2,"), "{}", out);
+        assert!(!out.contains("struct A "), "{}", out);
+        assert!(out.contains("pub b: B,"), "{}", out);
+    }
+
+    #[test]
+    fn merge_similar() {
+        let json = r#"{"home":{"street":"a","city":"b","zip":1},"work":{"street":"a","city":"b","floor":3}}"#;
+
+        let out = generate(json, options());
+        assert!(out.contains("pub home: Home,"), "{}", out);
+        assert!(out.contains("pub work: Work,"), "{}", out);
+
+        let opts = crate::Options {
+            merge_similar: Some(0.5),
+            ..options()
+        };
+        let out = generate(json, opts);
+        assert!(out.contains("pub home: Work,"), "{}", out);
+        assert!(out.contains("pub floor: Option<i64>,"), "{}", out);
+        assert!(out.contains("pub zip: Option<i64>,"), "{}", out);
+
+        // they only share half of their keys
+        let opts = crate::Options {
+            merge_similar: Some(0.8),
+            ..options()
+        };
+        assert!(generate(json, opts).contains("pub home: Home,"));
+    }
+
+    #[test]
+    fn required_threshold() {
+        let mut lines = vec![
+            r#"{"a":1,"b":true}"#,
+            r#"{"a":2,"b":true}"#,
+            r#"{"a":3,"b":false}"#,
+            r#"{"a":4}"#,
+        ];
+        let opts = |threshold| crate::Options {
+            input_mode: crate::InputMode::JsonLines,
+            required_threshold: threshold,
+            ..options()
+        };
+
+        let out = generate(&lines.join("\n"), opts(None));
+        assert!(out.contains("pub b: Option<bool>,"), "{}", out);
+        let out = generate(&lines.join("\n"), opts(Some(0.7)));
+        assert!(out.contains("pub b: bool,"), "{}", out);
+        let out = generate(&lines.join("\n"), opts(Some(0.8)));
+        assert!(out.contains("pub b: Option<bool>,"), "{}", out);
+
+        // a field that was null is never required
+        lines.push(r#"{"a":5,"b":null}"#);
+        let out = generate(&lines.join("\n"), opts(Some(0.7)));
+        assert!(out.contains("pub b: Option<bool>,"), "{}", out);
+    }
+
+    #[test]
+    fn double_option() {
+        let lines = [r#"{"a":1}"#, r#"{"a":null}"#, r#"{}"#].join("\n");
+        let opts = crate::Options {
+            input_mode: crate::InputMode::JsonLines,
+            double_option: true,
+            ..options()
+        };
+
+        let out = generate(&lines, opts);
+        assert!(
+            out.contains(concat!(
+                "    #[serde(default, with = \"::serde_with::rust::double_option\", ",
+                "skip_serializing_if = \"Option::is_none\")]\n",
+                "    pub a: Option<Option<i64>>,"
+            )),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let at = |path: &str, o: Override| Override {
+            path: path.into(),
+            ..o
+        };
+        let opts = crate::Options {
+            tuple_max: Some(3),
+            overrides: vec![
+                at(
+                    "$.items[].id",
+                    Override {
+                        kind: Some("uuid::Uuid".into()),
+                        rename: Some("identifier".into()),
+                        ..Override::default()
+                    },
+                ),
+                at(
+                    "$.items[].n",
+                    Override {
+                        raw: true,
+                        ..Override::default()
+                    },
+                ),
+                at(
+                    "$.meta",
+                    Override {
+                        name: Some("Metadata".into()),
+                        ..Override::default()
+                    },
+                ),
+                at(
+                    "$.extra",
+                    Override {
+                        map: true,
+                        ..Override::default()
+                    },
+                ),
+                at(
+                    "$.scores[*]",
+                    Override {
+                        kind: Some("f32".into()),
+                        ..Override::default()
+                    },
+                ),
+                at(
+                    "$.point[0]",
+                    Override {
+                        kind: Some("u8".into()),
+                        ..Override::default()
+                    },
+                ),
+            ],
+            ..options()
+        };
+
+        let json = r#"{
+            "items": [{"id": "x", "n": 1}],
+            "meta": {"a": 1, "b": "x"},
+            "extra": {"a": 1, "b": 2},
+            "scores": {"1": 1, "2": 2},
+            "point": [1, "a"]
+        }"#;
+        let out = generate(json, opts);
+        for expected in &[
+            "#[serde(rename = \"id\")]\n    pub identifier: uuid::Uuid,",
+            "pub n: ::serde_json::Value,",
+            "pub meta: Metadata,",
+            "pub struct Metadata {",
+            "pub extra: HashMap<String, i64>,",
+            "pub scores: HashMap<String, f32>,",
+            "pub struct Point(pub u8, pub String);",
+        ] {
+            assert!(out.contains(expected), "{}\n{}", expected, out);
+        }
+    }
+
+    #[test]
+    fn flatten_shared() {
+        let json = r#"{"user":{"id":1,"created":"a","name":"x"},"post":{"id":2,"created":"b","title":"y"}}"#;
+        let opts = |min_fields| crate::Options {
+            flatten_shared: Some(min_fields),
+            ..options()
+        };
+
+        let out = generate(json, opts(2));
+        assert_eq!(
+            out.matches("#[serde(flatten)]\n    pub shared: Shared,")
+                .count(),
+            2
+        );
+        assert!(
+            out.contains("pub struct Shared {\n    pub created: String,\n    pub id: i64,\n}"),
+            "{}",
+            out
+        );
+        assert!(out.contains("pub name: String,"), "{}", out);
+        assert!(out.contains("pub title: String,"), "{}", out);
+
+        // the structs only have two fields in common
+        assert!(!generate(json, opts(3)).contains("Shared"));
+    }
+
+    #[test]
+    fn generics() {
+        let json = r#"{
+            "user_page": {"data": [{"id": 1, "name": "a"}], "next_cursor": "x", "total": 1},
+            "post_page": {"data": [{"id": 1, "title": "t", "body": "b"}], "next_cursor": "x", "total": 1}
+        }"#;
+        let opts = || crate::Options {
+            generics: true,
+            ..options()
+        };
+
+        let out = generate(json, opts());
+        assert!(
+            out.contains("pub struct Page<T> {\n    pub data: Vec<T>,"),
+            "{}",
+            out
+        );
+        assert!(out.contains("pub post_page: Page<Data>,"), "{}", out);
+        assert!(out.contains("pub user_page: Page<Data2>,"), "{}", out);
+        assert!(!out.contains("struct UserPage"), "{}", out);
+
+        // without anything in common, the first struct names it
+        let json = json
+            .replace("user_page", "users")
+            .replace("post_page", "posts");
+        let out = generate(&json, opts());
+        assert!(out.contains("pub struct Posts<T> {"), "{}", out);
+        assert!(out.contains("pub users: Posts<Data2>,"), "{}", out);
+
+        // the root keeps its name
+        let json = r#"{"x": 1, "data": {"x": 2, "data": "a"}}"#;
+        let out = generate(json, opts());
+        assert!(!out.contains("<T>"), "{}", out);
+    }
+
+    #[test]
+    fn affixes() {
+        let tests: &[(&[&str], (&str, &str))] = &[
+            (&["Vec<User>", "Vec<Post>"], ("Vec<", ">")),
+            (&["Vec<UserItem>", "Vec<PostItem>"], ("Vec<", ">")),
+            (&["Option<Vec<A>>", "Option<Vec<B>>"], ("Option<Vec<", ">>")),
+            (&["User", "Post"], ("", "")),
+            (&["Vec<User>", "Option<User>"], ("", "<User>")),
+        ];
+        for (kinds, expected) in tests {
+            assert_eq!(
+                common_affixes(kinds.iter().copied()),
+                *expected,
+                "{:?}",
+                kinds
+            );
+        }
+
+        let tests: &[(&[&str], &str)] = &[
+            (&["UserPage", "PostPage"], "Page"),
+            (&["PageOfUsers", "PageOfPosts"], "PageOf"),
+            (&["UserPage", "UserPage"], "UserPage"),
+            (&["ApiUserResponse", "ApiPostResponse"], "ApiResponse"),
+            (&["Users", "Posts"], ""),
+        ];
+        for (names, expected) in tests {
+            assert_eq!(common_words(names), *expected, "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn replace_types() {
+        assert_eq!(replace_type("User", "User", "Page<A>"), "Page<A>");
+        assert_eq!(replace_type("Vec<User>", "User", "Page<A>"), "Vec<Page<A>>");
+        assert_eq!(
+            replace_type("HashMap<String, User>", "User", "Page<A>"),
+            "HashMap<String, Page<A>>"
+        );
+        assert_eq!(replace_type("UserItem", "User", "Page<A>"), "UserItem");
+        assert_eq!(replace_type("Vec<Users>", "User", "Page<A>"), "Vec<Users>");
+    }
+
+    #[test]
+    fn enums() {
+        let opts = || crate::Options {
+            enum_limit: Some(3),
+            ..options()
+        };
+
+        let sample = r#"[{"status":"open"},{"status":"in_review"},{"status":"open"}]"#;
+        let out = generate(sample, opts());
+        assert!(out.contains("pub status: Status,"), "{}", out);
+        assert!(
+            out.contains(
+                "pub enum Status {\n    #[serde(rename = \"open\")]\n    Open,\n    #[serde(rename = \"in_review\")]\n    InReview,\n}"
+            ),
+            "{}",
+            out
+        );
+
+        let out = generate(
+            sample,
+            crate::Options {
+                enum_other: true,
+                ..opts()
+            },
+        );
+        assert!(
+            out.contains("    InReview,\n    #[serde(other)]\n    Unknown,\n}"),
+            "{}",
+            out
+        );
+
+        // the values have to repeat
+        let out = generate(r#"[{"status":"open"},{"status":"closed"}]"#, opts());
+        assert!(out.contains("pub status: String,"), "{}", out);
+
+        // too many values
+        let out = generate(
+            r#"[{"status":"a"},{"status":"b"},{"status":"c"},{"status":"d"},{"status":"a"}]"#,
+            opts(),
+        );
+        assert!(out.contains("pub status: String,"), "{}", out);
+
+        // the values of a map don't have to be the same
+        let fields = (0..40)
+            .map(|i| format!(r#""k{}":"v{}""#, i, i))
+            .collect::<Vec<_>>();
+        let out = generate(&format!(r#"{{"labels":{{{}}}}}"#, fields.join(",")), opts());
+        assert!(
+            out.contains("pub labels: HashMap<String, String>,"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn big_numbers() {
+        let opts = crate::Options {
+            precision: crate::Precision::Wide,
+            ..options()
+        };
+        let sample = r#"{"id":18446744073709551615,"big":123456789012345678901234567890,"price":123456789012345678901.5}"#;
+        let out = generate(sample, opts);
+        assert!(out.contains("pub id: u64,"), "{}", out);
+        assert!(out.contains("pub big: u128,"), "{}", out);
+        assert!(out.contains("pub price: f64,"), "{}", out);
+    }
+
+    #[test]
+    fn dates() {
+        let sample = r#"[
+            {"created_at":1602936000,"deleted_at":"2020-10-17T12:00:00Z","sent_ms":1602936000000,
+             "day":"2020-10-17","arr_at":[1602936000],"seen":["2020-10-17T12:00:00Z"]},
+            {"sent_ms":1602936000001,"day":"2020-10-18","arr_at":[],"seen":[]}
+        ]"#;
+        let opts = |library| crate::Options {
+            dates: Some(library),
+            ..options()
+        };
+
+        let out = generate(sample, opts(crate::DateLibrary::Chrono));
+        for expected in &[
+            "// requires the 'chrono/serde' feature",
+            "    #[serde(default, with = \"chrono::serde::ts_seconds_option\")]\n    pub created_at: Option<chrono::DateTime<chrono::Utc>>,",
+            "    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,",
+            "    #[serde(with = \"chrono::serde::ts_milliseconds\")]\n    pub sent_ms: chrono::DateTime<chrono::Utc>,",
+            "    pub day: chrono::NaiveDate,",
+            // the adapters can't be used for the items of a vec
+            "    pub arr_at: Vec<i64>,",
+            "    pub seen: Vec<chrono::DateTime<chrono::Utc>>,",
+        ] {
+            assert!(out.contains(expected), "{}\n{}", expected, out);
+        }
+
+        let out = generate(sample, opts(crate::DateLibrary::Time));
+        for expected in &[
+            "// requires the 'time/serde-well-known' feature",
+            "    #[serde(default, with = \"time::serde::timestamp::option\")]\n    pub created_at: Option<time::OffsetDateTime>,",
+            "    #[serde(default, with = \"time::serde::rfc3339::option\")]\n    pub deleted_at: Option<time::OffsetDateTime>,",
+            "    #[serde(with = \"time::serde::timestamp::milliseconds\")]\n    pub sent_ms: time::OffsetDateTime,",
+            "    pub day: time::Date,",
+            "    pub arr_at: Vec<i64>,",
+            "    pub seen: Vec<String>,",
+        ] {
+            assert!(out.contains(expected), "{}\n{}", expected, out);
+        }
+    }
+
+    #[test]
+    fn recursion() {
+        let out = generate(
+            r#"{"name":"a","children":[{"name":"b","children":[]}],"next":{"name":"c","children":[],"next":null}}"#,
+            options(),
+        );
+        assert!(out.contains("pub children: Vec<Root>,"), "{}", out);
+        assert!(out.contains("pub next: Option<Box<Root>>,"), "{}", out);
+
+        // the same recursive struct is reused
+        let out = generate(
+            r#"{"left":{"name":"a","children":[{"name":"b","children":[]}]},"right":{"name":"c","children":[{"name":"d","children":[]}]}}"#,
+            options(),
+        );
+        assert!(out.contains("pub left: Right,"), "{}", out);
+        assert!(out.contains("pub children: Vec<Right>,"), "{}", out);
+        assert!(!out.contains("struct Left"), "{}", out);
+
+        // a generic struct keeps the references of the structs it holds
+        let out = generate(
+            r#"{"users":{"total":1,"data":{"name":"a","manager":{"name":"b","manager":null}}},
+                "posts":{"total":2,"data":{"title":"t","replies":[{"title":"r","replies":[]}]}}}"#,
+            crate::Options {
+                generics: true,
+                ..options()
+            },
+        );
+        assert!(out.contains("pub struct Posts<T> {"), "{}", out);
+        assert!(out.contains("pub users: Posts<Data2>,"), "{}", out);
+        assert!(out.contains("pub manager: Option<Box<Data2>>,"), "{}", out);
+        assert!(out.contains("pub replies: Vec<Data>,"), "{}", out);
+    }
+
+    #[test]
+    fn field_maps() {
+        let out = generate(
+            r#"{"id":1,"items":{"1":{"x":{"n":1,"m":"s"}},"2":{"x":{"n":2,"m":"t"}}}}"#,
+            options(),
+        );
+        assert!(
+            out.contains("pub items: HashMap<String, Items>,"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("pub struct Items {\n    pub x: X,\n}"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("pub struct X {\n    pub m: String,\n    pub n: i64,\n}"),
+            "{}",
+            out
+        );
+        assert!(!out.contains("Value"), "{}", out);
+
+        let out = generate(
+            r#"{"id":1,"items":{"1":[{"n":1,"m":"s"}],"2":[{"n":2,"m":"t"}]}}"#,
+            options(),
+        );
+        assert!(
+            out.contains("pub items: HashMap<String, Vec<Items>>,"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("pub struct Items {\n    pub m: String,"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let sample = r#"{"user":{"name":"x","email":"y"}}"#;
+        let out = generate(sample, options());
+        assert!(out.contains("pub user: User,"), "{}", out);
+
+        let out = generate(
+            sample,
+            crate::Options {
+                map_threshold: 2,
+                ..options()
+            },
+        );
+        assert!(
+            out.contains("pub user: HashMap<String, String>,"),
+            "{}",
+            out
+        );
+
+        let out = generate(
+            r#"{"scores":{"1":1,"2":2}}"#,
+            crate::Options {
+                infer_maps: false,
+                map_threshold: 2,
+                ..options()
+            },
+        );
+        assert!(out.contains("pub scores: Scores,"), "{}", out);
+        assert!(!out.contains("HashMap"), "{}", out);
+        // every integer has its own range, which doesn't make the values different
+        let fields = (0..40)
+            .map(|i| format!(r#""k{}":{}"#, i, i))
+            .collect::<Vec<_>>();
+        let sample = format!(r#"{{"scores":{{{}}}}}"#, fields.join(","));
+        for (policy, ty) in &[
+            (IntegerPolicy::Smallest, "u8"),
+            (IntegerPolicy::Unsigned, "u64"),
+        ] {
+            let opts = crate::Options {
+                integer_policy: *policy,
+                ..options()
+            };
+            let out = generate(&sample, opts);
+            let field = format!("pub scores: HashMap<String, {}>,", ty);
+            assert!(out.contains(&field), "{}", out);
+        }
+    }
+
+    #[test]
+    fn cluster_names() {
+        let opts = crate::Options {
+            cluster: Some(0.3),
+            ..options()
+        };
+        let out = generate(
+            r#"{"events":[{"id":1,"x":1},{"url":"a","duration":3},{"id":2,"x":2}]}"#,
+            opts,
+        );
+        assert!(
+            out.contains("    Id(EventsId),\n    Url(EventsUrl),"),
+            "{}",
+            out
+        );
+        assert!(out.contains("pub struct EventsUrl {"), "{}", out);
+
+        // a single object is still just an object
+        let out = generate(r#"[{"v":1},{"v":{"a":1,"b":"c"}}]"#, options());
+        assert!(out.contains("Object(VObject),"), "{}", out);
+    }
+
+    #[test]
+    fn external_tags() {
+        let out = generate(r#"[{"Circle":{"r":1.0}},{"Square":{"side":2}}]"#, options());
+        assert!(out.contains("pub enum Root {"), "{}", out);
+        assert!(out.contains("Circle(Circle),"), "{}", out);
+
+        let out = generate(
+            r#"{"shapes":[{"Circle":{"r":1.0}},{"Square":{"side":2}}],"other":1}"#,
+            options(),
+        );
+        assert!(out.contains("pub shapes: Vec<Shapes>,"), "{}", out);
+        assert!(out.contains("pub enum Shapes {"), "{}", out);
+
+        // the root object itself is never an enum
+        let lines = [r#"{"Circle":{"r":1.0}}"#, r#"{"Square":{"side":2}}"#].join("\n");
+        let opts = crate::Options {
+            input_mode: crate::InputMode::JsonLines,
+            ..options()
+        };
+        let out = generate(&lines, opts);
+        assert!(out.contains("pub struct Root {"), "{}", out);
+        assert!(!out.contains("enum"), "{}", out);
+    }
+
+    #[test]
+    fn placeholders() {
+        let json = r#"{"tags":[],"deleted":null,"a":1}"#;
+
+        let out = generate(json, options());
+        assert!(out.contains("pub deleted: Option<Deleted>,"), "{}", out);
+        assert!(out.contains("pub tags: Vec<TagsItem>,"), "{}", out);
+        assert!(out.contains("pub type Deleted = ::serde_json::Value; // TODO: was always null"));
+        assert!(
+            out.contains("pub type TagsItem = ::serde_json::Value; // TODO: was never observed")
+        );
+
+        let opts = crate::Options {
+            placeholder: Some("()".into()),
+            ..options()
+        };
+        let out = generate(json, opts);
+        assert!(out.contains("pub deleted: Option<()>,"), "{}", out);
+        assert!(out.contains("pub tags: Vec<()>,"), "{}", out);
+        assert!(!out.contains("pub type"), "{}", out);
+
+        // the items of an empty top-level array are the root
+        let out = generate("[]", options());
+        assert!(out.contains("pub list: Vec<Root>,"), "{}", out);
+        assert!(
+            out.contains("pub type Root = ::serde_json::Value; // TODO: was never observed"),
+            "{}",
+            out
+        );
+
+        let opts = options();
+        let mut g = Generator::new(&opts);
+        let shape = Shape::Array(Box::new(Shape::Bottom));
+        g.walk(&shape, &Wrapper::default(), "Root", &mut false);
+        assert_eq!(g.root.as_deref(), Some("Root"));
+        assert_eq!(g.warnings.len(), 1);
+        assert!(
+            g.warnings[0].starts_with("'$[]' was never observed"),
+            "{:?}",
+            g.warnings
+        );
+    }
+
+    #[test]
+    fn tagged_variants() {
+        let out = generate(
+            r#"[{"type":"circle","r":1},{"type":"square","side":2}]"#,
+            options(),
+        );
+        assert!(out.contains(r#"#[serde(tag = "type")]"#), "{}", out);
+        assert!(out.contains("Circle(Circle)"), "{}", out);
+
+        // the variants mostly have the same fields, so it's a single struct
+        let out = generate(
+            r#"[{"type":"png","w":1,"h":2},{"type":"jpeg","w":1,"h":2,"q":3},{"type":"txt","w":1,"h":3}]"#,
+            options(),
+        );
+        assert!(!out.contains("enum"), "{}", out);
+        assert!(out.contains("pub q: Option<i64>,"), "{}", out);
+    }
+}
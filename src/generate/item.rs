@@ -1,21 +1,49 @@
 use super::Print;
 use crate::Options;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Struct {
     pub rename: Option<String>,
     pub name: String,
     pub fields: Vec<Field>,
+    /// whether this is a tuple struct, its fields are kept in order
+    pub tuple: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
     pub rename: Option<String>,
     pub default: bool,
+    pub with: Option<String>,
+    /// whether the field is left out when it's `None`
+    pub skip_none: bool,
+    /// printed above the field, e.g. how often it was present
+    pub comment: Option<String>,
+    /// whether the fields of this type are embedded with `#[serde(flatten)]`
+    pub flatten: bool,
     pub binding: String,
     pub kind: String,
 }
 
+/// a type alias, e.g. for a placeholder type
+#[derive(Debug, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    pub kind: String,
+    /// printed after the alias
+    pub comment: Option<String>,
+}
+
+impl Print for Alias {
+    fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, _: &Options) -> super::IoResult {
+        write!(writer, "pub type {} = {};", self.name, self.kind)?;
+        match &self.comment {
+            Some(comment) => writeln!(writer, " // {}", comment),
+            None => writeln!(writer),
+        }
+    }
+}
+
 impl Print for Struct {
     fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, opts: &Options) -> super::IoResult {
         writeln!(writer, "#[derive({})]", &opts.default_derives)?;
@@ -24,6 +52,10 @@ impl Print for Struct {
             writeln!(writer, "#[serde(rename = \"{}\")]", rename)?;
         }
 
+        if self.tuple {
+            return self.print_tuple(writer);
+        }
+
         writeln!(writer, "pub struct {} {{", self.name)?;
 
         let fields = {
@@ -33,38 +65,136 @@ impl Print for Struct {
         };
 
         for field in fields {
-            match (field.default, &field.rename) {
-                (true, Some(rename)) => {
-                    writeln!(writer, "    #[serde(default, rename = \"{}\")]", rename)?;
+            if let Some(comment) = &field.comment {
+                writeln!(writer, "    // {}", comment)?;
+            }
+
+            let mut attrs = Vec::new();
+            if field.flatten {
+                attrs.push("flatten".to_string());
+            }
+            if field.default {
+                attrs.push("default".to_string());
+            }
+            if let Some(rename) = &field.rename {
+                attrs.push(format!("rename = \"{}\"", rename));
+            }
+            if let Some(with) = &field.with {
+                attrs.push(format!("with = \"{}\"", with));
+            }
+            if field.skip_none {
+                attrs.push("skip_serializing_if = \"Option::is_none\"".to_string());
+            }
+            print_attrs(writer, &attrs)?;
+
+            writeln!(writer, "    pub {}: {},", field.binding, field.kind)?;
+        }
+
+        writeln!(writer, "}}")
+    }
+}
+
+impl Struct {
+    fn print_tuple<W: std::io::Write + ?Sized>(&self, writer: &mut W) -> super::IoResult {
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| {
+                let mut attrs = Vec::new();
+                if field.default {
+                    attrs.push("default".to_string());
                 }
-                (true, None) => {
-                    writeln!(writer, "    #[serde(default)]")?;
+                if let Some(with) = &field.with {
+                    attrs.push(format!("with = \"{}\"", with));
                 }
-                (false, Some(rename)) => {
-                    writeln!(writer, "    #[serde(rename = \"{}\")]", rename)?;
+
+                match attrs.as_slice() {
+                    [] => format!("pub {}", field.kind),
+                    attrs => format!("#[serde({})] pub {}", attrs.join(", "), field.kind),
                 }
-                _ => {}
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(writer, "pub struct {}({});", self.name, fields.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub tagging: Tagging,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tagging {
+    External,
+    Internal(String),
+    Untagged,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub rename: Option<String>,
+    pub other: bool,
+    pub name: String,
+    pub kind: Option<String>,
+    pub with: Option<String>,
+}
+
+impl Print for Enum {
+    fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, opts: &Options) -> super::IoResult {
+        writeln!(writer, "#[derive({})]", &opts.default_derives)?;
+
+        match &self.tagging {
+            Tagging::External => {}
+            Tagging::Internal(tag) => writeln!(writer, "#[serde(tag = \"{}\")]", tag)?,
+            Tagging::Untagged => writeln!(writer, "#[serde(untagged)]")?,
+        }
+
+        writeln!(writer, "pub enum {} {{", self.name)?;
+
+        for variant in &self.variants {
+            let mut attrs = Vec::new();
+            if variant.other {
+                attrs.push("other".to_string());
+            }
+            if let Some(rename) = &variant.rename {
+                attrs.push(format!("rename = \"{}\"", rename));
             }
+            if let Some(with) = &variant.with {
+                attrs.push(format!("with = \"{}\"", with));
+            }
+            print_attrs(writer, &attrs)?;
 
-            writeln!(writer, "    pub {}: {},", field.binding, field.kind)?;
+            match &variant.kind {
+                Some(kind) => writeln!(writer, "    {}({}),", variant.name, kind)?,
+                None => writeln!(writer, "    {},", variant.name)?,
+            }
         }
 
         writeln!(writer, "}}")
     }
 }
 
+fn print_attrs<W: std::io::Write + ?Sized>(writer: &mut W, attrs: &[String]) -> super::IoResult {
+    if attrs.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "    #[serde({})]", attrs.join(", "))
+}
+
 #[derive(Debug)]
 pub struct Item {
     pub ident: String,
     pub body: Vec<String>,
+    /// the module used with `#[serde(with = "...")]` for this type
+    pub with: Option<String>,
 }
 
 impl Print for Item {
     fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, _: &Options) -> super::IoResult {
         write!(writer, "{}", self.ident)?;
-        self.body
-            .iter()
-            .map(|el| write!(writer, "{}", el))
-            .collect()
+        self.body.iter().try_for_each(|el| write!(writer, "{}", el))
     }
 }
@@ -1,51 +1,106 @@
 use super::{
     generator::Generator,
-    item::{Item, Struct},
+    item::{Alias, Enum, Item, Struct},
     Print,
 };
-use crate::{generate, infer::Shape, util::Wrapper, CasingScheme, Options};
+use crate::{
+    generate,
+    infer::{self, Conflict, Shape},
+    util::{self, Wrapper},
+    CasingScheme, InputMode, Kind, Options,
+};
 
 use json::JsonValue as Value;
-use std::io::Write;
+use std::{collections::BTreeSet, io::Write};
 
 #[derive(Debug)]
 pub struct Program<'a> {
     items: Vec<Item>,
     structs: Vec<Struct>,
+    enums: Vec<Enum>,
+    aliases: Vec<Alias>,
+    root: Option<String>,
     wrap_in_vec: Option<Struct>,
     opts: &'a Options,
     data: &'a str,
 
     should_include_map: bool,
+    imports: BTreeSet<String>,
+    features: BTreeSet<String>,
+
+    notes: Vec<String>,
+    warnings: Vec<String>,
 }
 
 impl<'a> Program<'a> {
-    pub fn generate(val: Value, data: &'a str, opts: &'a Options) -> Self {
+    pub fn generate(
+        values: Vec<Value>,
+        integers: &infer::Integers,
+        data: &'a str,
+        opts: &'a Options,
+    ) -> anyhow::Result<Self> {
         let root_name = opts.root_name.clone();
-        let tuple_max = opts.tuple_max;
 
         let mut g = Generator::new(opts);
-        let shape = Shape::new(&val, tuple_max.unwrap_or_default());
+        // every document is folded into a single shape
+        let shape = match opts.input_mode {
+            InputMode::Schema => Shape::fold(
+                values
+                    .iter()
+                    .map(|schema| infer::from_schema(schema, opts))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            ),
+            _ => Shape::fold(values.iter().map(|val| Shape::new(val, opts, integers))),
+        };
+        let shape = shape
+            .unify(&opts.unify_policy, &mut vec![], &mut g.notes)
+            .map_err(|conflict| conflict_error(&values, conflict))?;
+        let shape = if opts.detect_recursion {
+            shape.find_recursion()
+        } else {
+            shape
+        };
 
         g.walk(&shape, &Wrapper::default(), &root_name, &mut false);
+        if opts.generics {
+            g.extract_generics();
+        }
+        if let Some(min_fields) = opts.flatten_shared {
+            g.flatten_shared(min_fields);
+        }
 
         let Generator {
             structs,
+            enums,
+            aliases,
+            root,
             wrap_in_vec,
             items,
             should_include_map,
+            imports,
+            features,
+            notes,
+            warnings,
             ..
         } = g;
 
-        Self {
+        Ok(Self {
+            root,
             wrap_in_vec,
             items,
             structs,
+            enums,
+            aliases,
             opts,
             data,
 
             should_include_map,
-        }
+            imports,
+            features,
+
+            notes,
+            warnings,
+        })
     }
 
     fn is_wrapped(&self) -> bool {
@@ -60,12 +115,12 @@ impl<'a> Program<'a> {
         }
     }
 
-    fn get_root(&self) -> Option<&Struct> {
-        self.structs.last()
+    fn get_root(&self) -> Option<&str> {
+        self.root.as_deref()
     }
 
     fn make_name_binding(&self) -> Option<(String, String)> {
-        let name = self.get_root().as_ref().map(|s| &s.name)?;
+        let name = self.get_root()?;
 
         let mut type_name = name.to_string();
         let binding = CasingScheme::Snake.convert(&type_name);
@@ -96,23 +151,24 @@ impl<'a> Program<'a> {
             type_name,
         })
     }
-}
 
-impl<'a> Print for Program<'a> {
-    fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, opts: &Options) -> super::IoResult {
-        if self.structs.is_empty() {
-            for item in &self.items {
-                write!(writer, "// ")?;
-                item.print(writer, opts)?;
-                writeln!(writer)?;
-            }
-            return Ok(());
+    fn print_types<W: std::io::Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        opts: &Options,
+    ) -> super::IoResult {
+        for feature in &self.features {
+            writeln!(writer, "// requires the '{}' feature", feature)?;
         }
 
         if self.should_include_map {
             writeln!(writer, "use std::collections::HashMap;")?;
         }
 
+        for import in &self.imports {
+            writeln!(writer, "use {};", import)?;
+        }
+
         writeln!(writer, "use ::serde::{{Serialize, Deserialize}};")?;
         writeln!(writer)?;
 
@@ -126,14 +182,62 @@ impl<'a> Print for Program<'a> {
             writeln!(writer)?;
         }
 
-        if self.opts.make_unit_test {
+        for item in self.enums.iter().rev() {
+            item.print(writer, opts)?;
+            writeln!(writer)?;
+        }
+
+        for alias in &self.aliases {
+            alias.print(writer, opts)?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Print for Program<'a> {
+    fn print<W: std::io::Write + ?Sized>(&self, writer: &mut W, opts: &Options) -> super::IoResult {
+        // a root without any types of its own (e.g. a map of integers) is just shown
+        let has_types =
+            !(self.structs.is_empty() && self.enums.is_empty() && self.aliases.is_empty());
+
+        if has_types {
+            self.print_types(writer, opts)?;
+        } else {
+            for item in &self.items {
+                write!(writer, "// ")?;
+                item.print(writer, opts)?;
+                writeln!(writer)?;
+            }
+        }
+
+        for note in &self.notes {
+            eprintln!("NOTE: {}", note);
+        }
+
+        for warning in &self.warnings {
+            eprintln!("WARNING: {}", warning);
+        }
+
+        if !has_types {
+            return Ok(());
+        }
+
+        // a schema isn't a sample of the type
+        let has_sample = self.opts.input_mode != InputMode::Schema;
+        if (self.opts.make_unit_test || self.opts.make_main) && !has_sample {
+            eprintln!("WARNING: cannot create unit tests or a main function from a schema");
+        }
+
+        if self.opts.make_unit_test && has_sample {
             match self.make_unit_test() {
                 Some(func) => func.print(writer, opts)?,
                 None => eprintln!("WARNING: cannot create unit test, cannot find root struct name"),
             };
         }
 
-        if self.opts.make_main {
+        if self.opts.make_main && has_sample {
             match self.make_main() {
                 Some(func) => func.print(writer, opts)?,
                 None => {
@@ -146,6 +250,78 @@ impl<'a> Print for Program<'a> {
     }
 }
 
+fn conflict_error(values: &[Value], conflict: Conflict) -> anyhow::Error {
+    let locate = |kind| {
+        values
+            .iter()
+            .enumerate()
+            .find_map(|(i, value)| {
+                let mut at = Vec::new();
+                locate(value, &conflict.path, kind, &mut at)
+                    .then(|| format!("{} in sample #{}", util::json_path(&at), i + 1))
+            })
+            .unwrap_or_else(|| "an unknown location".into())
+    };
+
+    anyhow::anyhow!(
+        "'{}' was both a {} and a {}\n  {}: {}\n  {}: {}",
+        util::json_path(&conflict.path),
+        conflict.left.name(),
+        conflict.right.name(),
+        conflict.left.name(),
+        locate(conflict.left),
+        conflict.right.name(),
+        locate(conflict.right),
+    )
+}
+
+/// finds the first value under the path with this kind, filling in the concrete path to it
+fn locate(value: &Value, path: &[String], kind: Kind, at: &mut Vec<String>) -> bool {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            let found = match value {
+                Value::Boolean(..) => Kind::Bool,
+                Value::Number(..) => Kind::Number,
+                Value::String(..) | Value::Short(..) => Kind::String,
+                Value::Array(..) => Kind::Array,
+                Value::Object(..) => Kind::Object,
+                Value::Null => return false,
+            };
+            return found == kind;
+        }
+    };
+
+    let mut nested = |segment: String, value: &Value| {
+        at.push(segment);
+        let found = locate(value, rest, kind, at);
+        if !found {
+            at.pop();
+        }
+        found
+    };
+
+    match (segment.as_str(), value) {
+        ("[]", Value::Array(els)) => els
+            .iter()
+            .enumerate()
+            .any(|(i, el)| nested(format!("[{}]", i), el)),
+        ("[*]", Value::Object(map)) => map.iter().any(|(key, el)| nested(key.to_string(), el)),
+        (index, Value::Array(els)) if index.starts_with('[') => index
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| els.get(i))
+            .into_iter()
+            .any(|el| nested(index.to_string(), el)),
+        (key, Value::Object(map)) => map
+            .get(key)
+            .into_iter()
+            .any(|el| nested(key.to_string(), el)),
+        _ => false,
+    }
+}
+
 struct MainFunction<'a> {
     sample: &'a str,
     type_name: String,
@@ -216,3 +392,77 @@ fn ensure_{binding}_from_sample() {{
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{generate, options};
+
+    #[test]
+    fn enum_root() {
+        let opts = || crate::Options {
+            make_unit_test: true,
+            ..options()
+        };
+
+        let out = generate(r#"[{"type":"a","x":1},{"type":"b","y":"s"}]"#, opts());
+        assert!(out.contains("pub enum Root {"), "{}", out);
+        assert!(out.contains("let r: Vec<Root> ="), "{}", out);
+
+        let out = generate(
+            r#"[{"id":1,"x":1},{"url":"a","duration":3}]"#,
+            crate::Options {
+                cluster: Some(0.3),
+                ..opts()
+            },
+        );
+        assert!(out.contains("let r: Vec<Root> ="), "{}", out);
+    }
+
+    #[test]
+    fn roots() {
+        let out = generate(r#"[1,"a",true]"#, options());
+        assert!(out.contains("pub list: Vec<Root>,"), "{}", out);
+        assert!(
+            out.contains("#[serde(untagged)]\npub enum Root {"),
+            "{}",
+            out
+        );
+
+        let opts = crate::Options {
+            enum_limit: Some(3),
+            ..options()
+        };
+        let out = generate(r#"["a","b","a"]"#, opts);
+        assert!(out.contains("pub enum Root {"), "{}", out);
+
+        let out = generate(r#"{"1":1,"2":2}"#, options());
+        assert!(
+            out.contains("pub type Root = HashMap<String, i64>;"),
+            "{}",
+            out
+        );
+
+        let out = generate(r#"{"1":{"a":1},"2":{"a":2}}"#, options());
+        assert!(
+            out.contains("pub type Root = HashMap<String, RootValue>;"),
+            "{}",
+            out
+        );
+        assert!(out.contains("pub struct RootValue {"), "{}", out);
+
+        let opts = crate::Options {
+            input_mode: crate::InputMode::Schema,
+            ..options()
+        };
+        let schema = r#"{"type":"object","additionalProperties":{"type":"string"}}"#;
+        let out = generate(schema, opts);
+        assert!(
+            out.contains("pub type Root = HashMap<String, String>;"),
+            "{}",
+            out
+        );
+
+        // nothing to name
+        assert_eq!(generate("[1,2]", options()), "// Vec<i64>\n");
+    }
+}
@@ -1,8 +1,13 @@
 pub type HashMap<K, V> = indexmap::IndexMap<K, V>;
 pub type Map = HashMap<String, Shape>;
+pub type Set = indexmap::IndexSet<String>;
+/// the parts of numbers, as given by the parser
+pub type Integers = std::collections::HashSet<(bool, u64, i16)>;
 
-mod local;
+mod format;
+mod schema;
 mod shape;
 
-pub use local::Local;
-pub use shape::Shape;
+pub use format::Time;
+pub use schema::from_schema;
+pub use shape::{integer_literals, similarity, Conflict, Presence, Shape};
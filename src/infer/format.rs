@@ -0,0 +1,208 @@
+use super::Shape;
+use crate::{CasingScheme, StringFormat};
+
+impl StringFormat {
+    pub(crate) fn matches(self, input: &str) -> bool {
+        match self {
+            Self::Uuid => is_uuid(input),
+            Self::Url => is_url(input),
+            Self::Ip => input.parse::<std::net::IpAddr>().is_ok(),
+            Self::Email => is_email(input),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Time {
+    /// an RFC 3339 date and time with an offset
+    Rfc3339,
+    /// a plain date, e.g. 2020-10-17
+    Date,
+    /// seconds since the unix epoch
+    Seconds,
+    /// milliseconds since the unix epoch
+    Milliseconds,
+}
+
+impl Time {
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.as_bytes();
+        if is_datetime(input) {
+            Some(Self::Rfc3339)
+        } else if input.len() == 10 && is_date(input) {
+            Some(Self::Date)
+        } else {
+            None
+        }
+    }
+
+    /// integers are only timestamps if the field name hints at it and the
+    /// value is within a reasonable range (1973 to 5138 for seconds)
+    pub fn from_epoch(key: &str, value: u64) -> Option<Self> {
+        const HINTS: &[&str] = &["at", "on", "time", "timestamp", "ts", "date", "ms"];
+
+        let key = CasingScheme::Snake.convert(key);
+        let hint = key.rsplit('_').next()?;
+        if !HINTS.contains(&hint) {
+            return None;
+        }
+
+        match value {
+            100_000_000..=99_999_999_999 if hint != "ms" => Some(Self::Seconds),
+            100_000_000_000..=99_999_999_999_999 => Some(Self::Milliseconds),
+            _ => None,
+        }
+    }
+
+    /// the shape used if this isn't always a timestamp
+    pub fn fallback(self) -> Shape {
+        match self {
+            Self::Rfc3339 | Self::Date => Shape::String,
+            Self::Seconds | Self::Milliseconds => Shape::Integer,
+        }
+    }
+}
+
+fn is_date(input: &[u8]) -> bool {
+    let digits = |range: std::ops::Range<usize>| input[range].iter().all(u8::is_ascii_digit);
+
+    input.len() >= 10
+        && digits(0..4)
+        && input[4] == b'-'
+        && digits(5..7)
+        && matches!(&input[5..7], [b'0', b'1'..=b'9'] | [b'1', b'0'..=b'2'])
+        && input[7] == b'-'
+        && digits(8..10)
+        && matches!(
+            &input[8..10],
+            [b'0', b'1'..=b'9'] | [b'1'..=b'2', _] | [b'3', b'0'..=b'1']
+        )
+}
+
+fn is_datetime(input: &[u8]) -> bool {
+    let digits = |range: std::ops::Range<usize>| input[range].iter().all(u8::is_ascii_digit);
+
+    let time = input.len() >= 20
+        && is_date(input)
+        && matches!(input[10], b'T' | b't' | b' ')
+        && digits(11..13)
+        && input[13] == b':'
+        && digits(14..16)
+        && input[16] == b':'
+        && digits(17..19);
+    if !time {
+        return false;
+    }
+
+    let mut rest = &input[19..];
+    if let [b'.', tail @ ..] = rest {
+        let fraction = tail.iter().take_while(|c| c.is_ascii_digit()).count();
+        if fraction == 0 {
+            return false;
+        }
+        rest = &tail[fraction..];
+    }
+
+    match rest {
+        [b'Z'] | [b'z'] => true,
+        [b'+', h1, h2, b':', m1, m2] | [b'-', h1, h2, b':', m1, m2] => {
+            [h1, h2, m1, m2].iter().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+pub fn is_uuid(input: &str) -> bool {
+    input.len() == 36
+        && input.bytes().enumerate().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == b'-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn is_url(input: &str) -> bool {
+    let (scheme, rest) = match input.find("://") {
+        Some(pos) => (&input[..pos], &input[pos + 3..]),
+        None => return false,
+    };
+
+    let mut scheme = scheme.chars();
+    matches!(scheme.next(), Some(c) if c.is_ascii_alphabetic())
+        && scheme.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !rest.contains(char::is_whitespace)
+}
+
+fn is_email(input: &str) -> bool {
+    let mut parts = input.split('@');
+    let (local, domain) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(local), Some(domain), None) => (local, domain),
+        _ => return false,
+    };
+
+    !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !input.contains(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        use StringFormat::*;
+        let tests = &[
+            (Uuid, "0b9f5c3e-0d6a-4a7e-9a52-3f1c2b7d8e90", true),
+            (Uuid, "0b9f5c3e0d6a4a7e9a523f1c2b7d8e90", false),
+            (Url, "https://example.com/foo?bar=baz", true),
+            (Url, "ftp://example.com", true),
+            (Url, "example.com", false),
+            (Url, "https://", false),
+            (Ip, "127.0.0.1", true),
+            (Ip, "::1", true),
+            (Ip, "fe80::1ff:fe23:4567:890a", true),
+            (Ip, "256.0.0.1", false),
+            (Email, "foo@example.com", true),
+            (Email, "foo@bar@example.com", false),
+            (Email, "foo@localhost", false),
+            (Email, "foo bar@example.com", false),
+        ];
+
+        for (format, input, expected) in tests {
+            assert_eq!(format.matches(input), *expected, "{:?}: {}", format, input);
+        }
+    }
+
+    #[test]
+    fn times() {
+        let tests = &[
+            ("2020-10-17T12:34:56Z", Some(Time::Rfc3339)),
+            ("2020-10-17T12:34:56.789+02:00", Some(Time::Rfc3339)),
+            ("2020-10-17 12:34:56-05:00", Some(Time::Rfc3339)),
+            ("2020-10-17T12:34:56", None),
+            ("2020-10-17", Some(Time::Date)),
+            ("2020-13-17", None),
+            ("2020-10-32", None),
+            ("20201017", None),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(Time::parse(input), *expected, "{}", input);
+        }
+
+        let tests = &[
+            ("created_at", 1_602_937_296, Some(Time::Seconds)),
+            ("createdAt", 1_602_937_296_000, Some(Time::Milliseconds)),
+            ("timestamp", 1_602_937_296, Some(Time::Seconds)),
+            ("expires_ms", 1_602_937_296, None),
+            ("expires_ms", 1_602_937_296_000, Some(Time::Milliseconds)),
+            ("latency_ms", 35, None),
+            ("count", 1_602_937_296, None),
+        ];
+        for (key, value, expected) in tests {
+            assert_eq!(Time::from_epoch(key, *value), *expected, "{}", key);
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use super::{Integers, Map, Presence, Set, Shape, Time};
+use crate::{Options, StringFormat};
+use anyhow::Context as _;
+use json::JsonValue as Value;
+
+/// builds the shape that a json schema (draft 7 or 2020-12) describes
+///
+/// definitions used with `$ref` keep their name, so they become a single named type
+pub fn from_schema(schema: &Value, opts: &Options) -> anyhow::Result<Shape> {
+    let mut converter = Converter {
+        root: schema,
+        opts,
+        refs: vec!["#"],
+        objects: Vec::new(),
+    };
+
+    // the root is named by the options
+    match converter.convert(schema, Some("#"))? {
+        Shape::Named(_, shape) => Ok(*shape),
+        shape => Ok(shape),
+    }
+}
+
+struct Converter<'a> {
+    root: &'a Value,
+    opts: &'a Options,
+    /// the references that are being converted, a reference back to one of them is a cycle
+    refs: Vec<&'a str>,
+    /// the reference that each of the objects being converted came from
+    objects: Vec<Option<&'a str>>,
+}
+
+impl<'a> Converter<'a> {
+    /// `owner` is the reference that points at this schema, if any
+    fn convert(&mut self, schema: &'a Value, owner: Option<&'a str>) -> anyhow::Result<Shape> {
+        match schema {
+            // `true` allows anything, and `false` allows nothing
+            Value::Boolean(true) => return Ok(Shape::Any),
+            Value::Boolean(false) => return Ok(Shape::Bottom),
+            Value::Object(..) => {}
+            other => anyhow::bail!("'{}' isn't a schema", other.dump()),
+        }
+
+        if let Some(reference) = schema["$ref"].as_str() {
+            return self.reference(reference);
+        }
+
+        let mut shape = if schema["oneOf"].is_array() || schema["anyOf"].is_array() {
+            let members = schema["oneOf"]
+                .members()
+                .chain(schema["anyOf"].members())
+                .map(|member| self.convert(member, None))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Shape::one_of(members)
+        } else if schema["allOf"].is_array() {
+            let members = schema["allOf"]
+                .members()
+                .map(|member| self.convert(member, owner))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            all_of(members)
+        } else if schema.has_key("enum") || schema.has_key("const") {
+            let values = match &schema["enum"] {
+                Value::Array(values) => values.iter().collect(),
+                _ => vec![&schema["const"]],
+            };
+            self.enumeration(&values)
+        } else {
+            let types = match &schema["type"] {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                ty => ty.as_str().into_iter().collect::<Vec<_>>(),
+            };
+
+            // without a type the keywords still tell what it is
+            let types = match types.as_slice() {
+                [] if schema.has_key("properties") || schema.has_key("additionalProperties") => {
+                    vec!["object"]
+                }
+                [] if schema.has_key("items") || schema.has_key("prefixItems") => vec!["array"],
+                [] => return Ok(Shape::Any),
+                _ => types,
+            };
+
+            let shapes = types
+                .into_iter()
+                .map(|ty| self.typed(ty, schema, owner))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Shape::fold(shapes)
+        };
+
+        // openapi marks nullable values this way
+        if schema["nullable"].as_bool() == Some(true) {
+            shape = shape.into_optional();
+        }
+        Ok(shape)
+    }
+
+    fn reference(&mut self, reference: &'a str) -> anyhow::Result<Shape> {
+        if self.refs.contains(&reference) {
+            // only a reference to the enclosing object can be a recursive type
+            let shape = match self.objects.last() {
+                Some(Some(owner)) if *owner == reference => Shape::Recursive,
+                _ => Shape::Any,
+            };
+            return Ok(shape);
+        }
+
+        let pointer = reference
+            .strip_prefix('#')
+            .with_context(|| format!("only local references are supported, not '{}'", reference))?;
+
+        let mut target = self.root;
+        for segment in pointer.split('/').skip(1) {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            target = match target {
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                Value::Object(..) if target.has_key(&segment) => Some(&target[segment.as_str()]),
+                _ => None,
+            }
+            .with_context(|| format!("cannot resolve '{}'", reference))?;
+        }
+
+        self.refs.push(reference);
+        let shape = self.convert(target, Some(reference));
+        self.refs.pop();
+
+        // the definition is named after the last segment, e.g. `#/$defs/Address`
+        match pointer.rsplit('/').next().filter(|name| !name.is_empty()) {
+            Some(name) => Ok(Shape::Named(name.to_string(), Box::new(shape?))),
+            None => shape,
+        }
+    }
+
+    fn typed(
+        &mut self,
+        ty: &str,
+        schema: &'a Value,
+        owner: Option<&'a str>,
+    ) -> anyhow::Result<Shape> {
+        let shape = match ty {
+            "null" => Shape::Null,
+            "boolean" => Shape::Bool,
+            "number" => Shape::Float,
+            "integer" => match (schema["minimum"].as_f64(), schema["maximum"].as_f64()) {
+                (Some(min), Some(max)) => Shape::Range(min as i128, max as i128),
+                _ => Shape::Integer,
+            },
+            "string" => self.string(schema["format"].as_str().unwrap_or_default()),
+            "array" => self.array(schema)?,
+            "object" => self.object(schema, owner)?,
+            ty => anyhow::bail!("unknown type '{}'", ty),
+        };
+        Ok(shape)
+    }
+
+    fn string(&self, format: &str) -> Shape {
+        let time = match format {
+            "date-time" => Some(Time::Rfc3339),
+            "date" => Some(Time::Date),
+            _ => None,
+        };
+        if let Some(time) = time.filter(|_| self.opts.dates.is_some()) {
+            return Shape::Time(time);
+        }
+
+        let opaque = self
+            .opts
+            .string_formats
+            .iter()
+            .find(|(string_format, _)| is_format(*string_format, format));
+        match opaque {
+            Some((_, ty)) => Shape::Opaque(ty.clone()),
+            None => Shape::String,
+        }
+    }
+
+    fn array(&mut self, schema: &'a Value) -> anyhow::Result<Shape> {
+        // draft 7 used `items` for tuples as well
+        let tuple = match (&schema["prefixItems"], &schema["items"]) {
+            (Value::Array(items), _) | (_, Value::Array(items)) => items,
+            (_, items) if items.is_null() => return Ok(Shape::Array(Box::new(Shape::Any))),
+            (_, items) => return Ok(Shape::Array(Box::new(self.convert(items, None)?))),
+        };
+
+        // the elements after the minimum length may be missing
+        let required = schema["minItems"].as_usize().unwrap_or(tuple.len());
+        let els = tuple
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let shape = self.convert(item, None)?;
+                Ok(if i < required {
+                    shape
+                } else {
+                    shape.into_optional()
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Shape::Tuple(els, 1))
+    }
+
+    fn object(&mut self, schema: &'a Value, owner: Option<&'a str>) -> anyhow::Result<Shape> {
+        let properties = &schema["properties"];
+        if properties.entries().next().is_none() {
+            let values = match &schema["additionalProperties"] {
+                Value::Null => Shape::Any,
+                additional => self.convert(additional, None)?,
+            };
+            return Ok(Shape::Map(Box::new(values)));
+        }
+
+        let required = schema["required"]
+            .members()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+
+        self.objects.push(owner);
+        let fields = properties
+            .entries()
+            .map(|(key, property)| {
+                let shape = self.convert(property, None)?;
+                let shape = if required.contains(&key) {
+                    shape
+                } else {
+                    shape.into_optional()
+                };
+                Ok((key.to_string(), shape))
+            })
+            .collect::<anyhow::Result<Map>>();
+        self.objects.pop();
+
+        // a schema doesn't observe anything, so there's no presence
+        Ok(Shape::Object(fields?, Presence::default()))
+    }
+
+    fn enumeration(&self, values: &[&Value]) -> Shape {
+        let (nulls, values) = values
+            .iter()
+            .partition::<Vec<&Value>, _>(|value| value.is_null());
+
+        let strings = values
+            .iter()
+            .map(|value| value.as_str().map(ToString::to_string));
+        let shape = match strings.collect::<Option<Set>>() {
+            Some(strings) => {
+                // a schema declares every value, so there's no limit
+                let count = strings.len() as u64;
+                Shape::Enum(strings, count, usize::MAX)
+            }
+            None => {
+                // the text of the schema isn't kept, so a big integer in an enum is a decimal
+                let integers = Integers::default();
+                let shapes = values
+                    .into_iter()
+                    .map(|value| Shape::new(value, self.opts, &integers));
+                Shape::fold(shapes)
+            }
+        };
+
+        match nulls.is_empty() {
+            true => shape,
+            false => shape.into_optional(),
+        }
+    }
+}
+
+/// the members of an `allOf` are combined into a single object
+fn all_of(members: Vec<Shape>) -> Shape {
+    let mut fields = Map::default();
+    let mut rest = Vec::new();
+    for member in members {
+        let member = match member {
+            // a `$ref` to an object is merged like the object itself
+            Shape::Named(_, shape) if matches!(*shape, Shape::Object(..)) => *shape,
+            member => member,
+        };
+        match member {
+            Shape::Object(map, _) => fields.extend(map),
+            other => rest.push(other),
+        }
+    }
+
+    match fields.is_empty() {
+        true => Shape::fold(rest),
+        false => {
+            Shape::fold(std::iter::once(Shape::Object(fields, Presence::default())).chain(rest))
+        }
+    }
+}
+
+/// the names of the `format` keyword for the string formats
+fn is_format(string_format: StringFormat, format: &str) -> bool {
+    match string_format {
+        StringFormat::Uuid => format == "uuid",
+        StringFormat::Url => matches!(format, "uri" | "url" | "iri"),
+        StringFormat::Ip => matches!(format, "ipv4" | "ipv6"),
+        StringFormat::Email => matches!(format, "email" | "idn-email"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(fields: &[(&str, Shape)]) -> Shape {
+        let fields = fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        Shape::Object(fields, Presence::default())
+    }
+
+    fn convert(schema: &str) -> Shape {
+        let schema = json::parse(schema).unwrap();
+        from_schema(&schema, &crate::tests::options()).unwrap()
+    }
+
+    fn optional(shape: Shape) -> Shape {
+        Shape::Optional(Box::new(shape))
+    }
+
+    #[test]
+    fn required() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer"},
+                "name": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "score": {"type": ["number", "null"]}
+            },
+            "required": ["id", "tags", "score"]
+        }"#;
+        let expected = object(&[
+            ("id", Shape::Integer),
+            ("name", optional(Shape::String)),
+            ("tags", Shape::Array(Box::new(Shape::String))),
+            ("score", optional(Shape::Float)),
+        ]);
+        assert_eq!(convert(schema), expected);
+    }
+
+    #[test]
+    fn references() {
+        let schema = r##"{
+            "$defs": {
+                "Node": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "children": {"type": "array", "items": {"$ref": "#/$defs/Node"}},
+                        "address": {"$ref": "#/$defs/Address"}
+                    },
+                    "required": ["name", "children"]
+                },
+                "Address": {
+                    "type": "object",
+                    "properties": {"street": {"type": "string"}},
+                    "required": ["street"]
+                }
+            },
+            "$ref": "#/$defs/Node"
+        }"##;
+        let address = object(&[("street", Shape::String)]);
+        let expected = object(&[
+            ("name", Shape::String),
+            ("children", Shape::Array(Box::new(Shape::Recursive))),
+            (
+                "address",
+                optional(Shape::Named("Address".into(), Box::new(address))),
+            ),
+        ]);
+        assert_eq!(convert(schema), expected);
+
+        let schema = json::parse(r##"{"$ref": "#/$defs/Missing"}"##).unwrap();
+        assert!(from_schema(&schema, &crate::tests::options()).is_err());
+    }
+
+    #[test]
+    fn all_of() {
+        let schema = r##"{
+            "$defs": {
+                "Base": {
+                    "type": "object",
+                    "properties": {"id": {"type": "integer"}, "note": {"type": "string"}},
+                    "required": ["id"]
+                }
+            },
+            "allOf": [
+                {"$ref": "#/$defs/Base"},
+                {"properties": {"email": {"type": "string"}}, "required": ["email"]}
+            ]
+        }"##;
+        let expected = object(&[
+            ("id", Shape::Integer),
+            ("note", optional(Shape::String)),
+            ("email", Shape::String),
+        ]);
+        assert_eq!(convert(schema), expected);
+    }
+
+    #[test]
+    fn one_of() {
+        let schema = r#"{
+            "oneOf": [
+                {"type": "object", "properties": {"card": {"type": "string"}}, "required": ["card"]},
+                {"type": "object", "properties": {"iban": {"type": "string"}}, "required": ["iban"]},
+                {"type": "null"}
+            ]
+        }"#;
+        let expected = optional(Shape::Union(vec![
+            object(&[("card", Shape::String)]),
+            object(&[("iban", Shape::String)]),
+        ]));
+        assert_eq!(convert(schema), expected);
+
+        let schema = r#"{"enum": ["a", "b", null]}"#;
+        let values = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            convert(schema),
+            optional(Shape::Enum(values.into_iter().collect(), 2, usize::MAX))
+        );
+    }
+
+    #[test]
+    fn additional_properties() {
+        let schema = r#"{"type": "object", "additionalProperties": {"type": "integer"}}"#;
+        assert_eq!(convert(schema), Shape::Map(Box::new(Shape::Integer)));
+
+        let schema = r#"{"type": "object"}"#;
+        assert_eq!(convert(schema), Shape::Map(Box::new(Shape::Any)));
+
+        // properties win over additional properties
+        let schema = r#"{
+            "type": "object",
+            "properties": {"id": {"type": "integer"}},
+            "additionalProperties": true
+        }"#;
+        assert_eq!(convert(schema), object(&[("id", optional(Shape::Integer))]));
+    }
+}
@@ -1,5 +1,7 @@
-use super::{HashMap, Map};
+use super::{format, HashMap, Integers, Map, Set, Time};
+use crate::{util, IntegerPolicy, Kind, Options, Precision, Resolution, UnifyPolicy};
 use json::JsonValue as Value;
+use std::borrow::Cow;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Shape {
@@ -12,49 +14,238 @@ pub enum Shape {
     Integer,
     Float,
     Array(Box<Self>),
-    Object(Map),
+    Object(Map, Presence),
     Tuple(Vec<Self>, u64),
     Map(Box<Self>),
+    /// a string that always parses as this type
     Opaque(String),
+    /// the distinct values of a string, how often it was seen, and the most values it can have
+    Enum(Set, u64, usize),
+    Tagged(String, Map),
+    Union(Vec<Self>),
+    Range(i128, i128),
+    /// an integer that doesn't fit in an i64 or u64, and whether it was negative
+    BigInteger(bool),
+    /// a decimal that doesn't fit in an f64 without losing precision
+    BigDecimal,
+    Time(Time),
+    /// a reference back to the object that contains it, e.g. the children of a tree
+    Recursive,
+    /// a shape with a name for its type, e.g. a definition from a schema
+    Named(String, Box<Self>),
+}
+
+/// a value that was seen with two kinds of types, which the policy doesn't allow
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub path: Vec<String>,
+    pub left: Kind,
+    pub right: Kind,
+}
+
+/// how many objects were seen, and how many of them had each key and had it as null
+#[derive(Clone, Debug, Default)]
+pub struct Presence {
+    pub seen: u64,
+    pub keys: HashMap<String, (u64, u64)>,
+    /// how many of the objects had exactly one key
+    pub single: u64,
+}
+
+// the counts are observations rather than structure, so they never make two shapes different
+impl PartialEq for Presence {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Presence {
+    fn new(fields: &Map) -> Self {
+        let keys = fields
+            .iter()
+            .map(|(k, v)| (k.clone(), (1, (*v == Shape::Null) as u64)))
+            .collect();
+        let single = (fields.len() == 1) as u64;
+        Self {
+            seen: 1,
+            keys,
+            single,
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.seen += other.seen;
+        self.single += other.single;
+        for (key, (present, null)) in other.keys {
+            let counts = self.keys.entry(key).or_default();
+            counts.0 += present;
+            counts.1 += null;
+        }
+        self
+    }
+
+    /// how often the key was present, and how often it was null
+    pub fn rates(&self, key: &str) -> (f64, f64) {
+        let (present, null) = self.keys.get(key).copied().unwrap_or_default();
+        let seen = self.seen.max(1) as f64;
+        (present as f64 / seen, null as f64 / seen)
+    }
 }
 
 impl Shape {
-    pub fn new(val: &Value, max_tuple: usize) -> Self {
+    pub fn new(val: &Value, opts: &Options, integers: &Integers) -> Self {
+        let max_tuple = opts.tuple_max.unwrap_or_default();
+
         match *val {
             Value::Null => Self::Null,
             Value::Boolean(..) => Self::Bool,
-            Value::Number(..) => {
-                if val.as_i64().is_some() {
-                    Self::Integer
-                } else {
-                    Self::Float
+            Value::Number(num) => match num.as_parts() {
+                _ if opts.precision == Precision::Lossy => Self::new_number(num, opts),
+                parts if integers.contains(&parts) => match parts {
+                    (true, mantissa, 0) if mantissa > i64::MAX as u64 => {
+                        Self::Range(mantissa as i128, mantissa as i128)
+                    }
+                    (false, mantissa, 0) if mantissa > i64::MAX as u64 + 1 => {
+                        Self::BigInteger(true)
+                    }
+                    (_, _, 0) => Self::new_number(num, opts),
+                    // the digits that didn't fit were dropped
+                    (positive, ..) => Self::BigInteger(!positive),
+                },
+                // the parser drops every digit after the mantissa overflows, even the fraction,
+                // so a number like this that wasn't written as an integer is a decimal
+                (_, mantissa, exponent) if exponent >= 0 && mantissa > i64::MAX as u64 => {
+                    Self::BigDecimal
                 }
-            }
+                // f64 can only represent 53 bits of the mantissa exactly
+                (_, mantissa, exponent) if exponent < 0 && mantissa > 1 << 53 => Self::BigDecimal,
+                _ => Self::new_number(num, opts),
+            },
+
+            Value::String(..) | Value::Short(..) => {
+                let value = val.as_str().unwrap_or_default();
+                let format = opts
+                    .string_formats
+                    .iter()
+                    .find(|(format, _)| format.matches(value));
+
+                let time = Some(value)
+                    .filter(|_| opts.dates.is_some())
+                    .and_then(Time::parse);
 
-            Value::String(..) | Value::Short(..) => Self::String,
+                match (format, time) {
+                    (Some((_, ty)), _) => Self::Opaque(ty.clone()),
+                    (None, Some(time)) => Self::Time(time),
+                    (None, None) => match opts.enum_limit {
+                        Some(limit) => {
+                            Self::Enum(std::iter::once(value.to_string()).collect(), 1, limit)
+                        }
+                        None => Self::String,
+                    },
+                }
+            }
             Value::Array(ref array) => {
                 let len = array.len();
                 if len > 1 && len <= max_tuple {
-                    Self::Tuple(
-                        array.iter().map(|s| Self::new(s, max_tuple)).collect(),
-                        len as _,
-                    )
+                    let shapes = array.iter().map(|s| Self::new(s, opts, integers));
+                    Self::Tuple(shapes.collect(), len as _)
                 } else {
-                    let ty = array.iter().fold(Self::Bottom, |left, v| {
-                        Self::factor(left, Self::new(v, max_tuple))
-                    });
+                    let shapes = array.iter().map(|v| Self::new(v, opts, integers));
+                    let ty = match opts.cluster {
+                        Some(threshold) => Self::cluster(shapes, threshold),
+                        None => Self::fold(shapes),
+                    };
                     Self::Array(Box::new(ty))
                 }
             }
             Value::Object(ref map) => {
                 let fields = map
                     .iter()
-                    .map(|(k, v)| (k.to_string(), Self::new(v, max_tuple)));
-                Self::Object(fields.collect())
+                    .map(|(k, v)| (k.to_string(), Self::new_field(k, v, opts, integers)))
+                    .collect();
+
+                if let Some(ty) = Self::infer_map(&fields, opts) {
+                    return Self::Map(Box::new(ty));
+                }
+
+                // if the object has a discriminator then we'll keep the fields under its value
+                let tag = opts
+                    .tag_keys
+                    .iter()
+                    .find_map(|key| map.get(key)?.as_str().map(|value| (key, value)));
+
+                match tag {
+                    Some((key, value)) => {
+                        let mut fields = fields;
+                        fields.shift_remove(key);
+
+                        // the tag is one of the keys of the object
+                        let mut presence = Presence::new(&fields);
+                        presence.single = (map.len() == 1) as u64;
+                        let mut variants = Map::default();
+                        variants.insert(value.to_string(), Self::Object(fields, presence));
+                        Self::Tagged(key.clone(), variants)
+                    }
+                    None => {
+                        let presence = Presence::new(&fields);
+                        Self::Object(fields, presence)
+                    }
+                }
+            }
+        }
+    }
+
+    fn new_field(key: &str, val: &Value, opts: &Options, integers: &Integers) -> Self {
+        let epoch = val
+            .as_u64()
+            .filter(|_| opts.dates.is_some())
+            .and_then(|value| Time::from_epoch(key, value));
+
+        match epoch {
+            Some(time) => Self::Time(time),
+            None => Self::new(val, opts, integers),
+        }
+    }
+
+    fn new_number(num: json::number::Number, opts: &Options) -> Self {
+        match num.as_parts() {
+            (_, _, exponent) if exponent != 0 => Self::Float,
+            _ if opts.integer_policy == IntegerPolicy::I64 => Self::Integer,
+            (positive, mantissa, _) => {
+                let n = if positive {
+                    mantissa as i128
+                } else {
+                    -(mantissa as i128)
+                };
+                Self::Range(n, n)
             }
         }
     }
 
+    /// determines whether this object is a dictionary rather than a record
+    ///
+    /// if every key looks generated (an id, a uuid, a hash or a date) or if
+    /// there are a lot of keys that all have the same shape then the object
+    /// is treated as a map and the folded value shape is returned
+    fn infer_map(fields: &Map, opts: &Options) -> Option<Self> {
+        if !opts.infer_maps || fields.is_empty() {
+            return None;
+        }
+
+        let dynamic_keys = fields.keys().all(|key| is_dynamic_key(key));
+        let many_keys = fields.len() >= opts.map_threshold && Self::is_uniform_fields(fields);
+
+        if !dynamic_keys && !many_keys {
+            return None;
+        }
+
+        match Self::fold(fields.values().cloned()) {
+            Self::Any => None,
+            ty => Some(ty),
+        }
+    }
+
+    // only used when debugging the factoring
     #[allow(dead_code)]
     pub(crate) fn root(&self) -> &'static str {
         match self {
@@ -67,10 +258,19 @@ impl Shape {
             Self::Integer => "Integer",
             Self::Float => "Float",
             Self::Array(_) => "Array",
-            Self::Object(_) => "Object",
+            Self::Object(..) => "Object",
             Self::Tuple(_, _) => "Tuple",
             Self::Map(_) => "Map",
             Self::Opaque(_) => "Opaque",
+            Self::Enum(..) => "Enum",
+            Self::Tagged(_, _) => "Tagged",
+            Self::Union(_) => "Union",
+            Self::Named(..) => "Named",
+            Self::Range(_, _) => "Range",
+            Self::BigInteger(_) => "BigInteger",
+            Self::BigDecimal => "BigDecimal",
+            Self::Time(_) => "Time",
+            Self::Recursive => "Recursive",
         }
     }
 
@@ -81,17 +281,63 @@ impl Shape {
     pub(crate) fn factor(left: Self, right: Self) -> Self {
         // eprintln!("{} | {}", left.root(), right.root());
 
-        if left == right {
+        // only short-circuit leaves, containers have to be walked so the
+        // observations inside of them (e.g. enum values) are collected
+        if left == right && left.is_leaf() {
             return left;
         }
 
-        // TODO sort tuple ('normalize')
         match (left, right) {
             // equal primitives
             (shape, Self::Bottom) | (Self::Bottom, shape) => shape,
 
             // promote int|float to float
             (Self::Integer, Self::Float) | (Self::Float, Self::Integer) => Self::Float,
+            // an integer that only fits in a u64 would lose its precision in an f64
+            (Self::Range(_, max), Self::Float) | (Self::Float, Self::Range(_, max))
+                if max > i64::MAX as i128 =>
+            {
+                Self::BigDecimal
+            }
+            (Self::Range(..), Self::Float) | (Self::Float, Self::Range(..)) => Self::Float,
+
+            // big numbers win over the smaller ones
+            (Self::BigInteger(left), Self::BigInteger(right)) => Self::BigInteger(left || right),
+            (Self::BigInteger(negative), Self::Integer)
+            | (Self::Integer, Self::BigInteger(negative)) => Self::BigInteger(negative),
+            (Self::BigInteger(negative), Self::Range(min, _))
+            | (Self::Range(min, _), Self::BigInteger(negative)) => {
+                Self::BigInteger(negative || min < 0)
+            }
+            (Self::BigDecimal, other) | (other, Self::BigDecimal)
+                if other.kind() == Some(Kind::Number) =>
+            {
+                Self::BigDecimal
+            }
+            (Self::BigInteger(..), Self::Float) | (Self::Float, Self::BigInteger(..)) => {
+                Self::BigDecimal
+            }
+
+            // a timestamp that wasn't always a timestamp
+            (Self::Time(time), other) | (other, Self::Time(time))
+                if other.kind() == time.fallback().kind() =>
+            {
+                Self::factor(time.fallback(), other)
+            }
+
+            // these shouldn't be mixed, but an integer is always an integer. an `Integer` can be
+            // negative, so with a value that only fits in a u64 neither of them fits
+            (Self::Integer, Self::Range(_, max)) | (Self::Range(_, max), Self::Integer)
+                if max > i64::MAX as i128 =>
+            {
+                Self::BigInteger(true)
+            }
+            (Self::Integer, Self::Range(..)) | (Self::Range(..), Self::Integer) => Self::Integer,
+
+            // widen the observed range of integers
+            (Self::Range(left_min, left_max), Self::Range(right_min, right_max)) => {
+                Self::Range(left_min.min(right_min), left_max.max(right_max))
+            }
 
             // nulls are represented by optionals
             (shape, Self::Null) | (Self::Null, shape) => shape.into_optional(),
@@ -101,20 +347,38 @@ impl Shape {
                 Self::factor(left, *right).into_optional()
             }
 
+            // the same named type, otherwise the name doesn't hold anymore
+            (Self::Named(left_name, left), Self::Named(right_name, right))
+                if left_name == right_name =>
+            {
+                Self::Named(left_name, Box::new(Self::factor(*left, *right)))
+            }
+            (Self::Named(_, named), other) | (other, Self::Named(_, named)) => {
+                Self::factor(*named, other)
+            }
+
             // tuple, tuple
             (Self::Tuple(left, left_arity), Self::Tuple(right, right_arity)) => {
                 if left.len() == right.len() {
                     let shapes = left
                         .into_iter()
-                        .zip(right.into_iter())
+                        .zip(right)
                         .map(|(l, r)| Self::factor(l, r))
                         .collect();
                     Self::Tuple(shapes, left_arity + right_arity)
                 } else {
-                    Self::Array(Box::new(Self::factor(Self::fold(left), Self::fold(right))))
+                    Self::factor_tuples(left, right, left_arity + right_arity)
                 }
             }
 
+            // an empty array is a tuple where every element is missing
+            (Self::Tuple(els, arity), Self::Array(ty))
+            | (Self::Array(ty), Self::Tuple(els, arity))
+                if *ty == Self::Bottom =>
+            {
+                Self::Tuple(els.into_iter().map(Self::into_optional).collect(), arity)
+            }
+
             // tuple, array | array, tuple
             (Self::Tuple(els, ..), Self::Array(ty)) | (Self::Array(ty), Self::Tuple(els, ..)) => {
                 Self::Array(Box::new(Self::factor(*ty, Self::fold(els))))
@@ -125,27 +389,221 @@ impl Shape {
                 Self::Array(Box::new(Self::factor(*left, *right)))
             }
 
+            // collect the observed values of both enums, until there are too many of them
+            (Self::Enum(mut left, left_count, limit), Self::Enum(right, right_count, _)) => {
+                left.extend(right);
+                match left.len() > limit {
+                    true => Self::String,
+                    false => Self::Enum(left, left_count + right_count, limit),
+                }
+            }
+
+            // an enum is just a more specific string
+            (Self::Enum(..), Self::String) | (Self::String, Self::Enum(..)) => Self::String,
+
+            // collect the variants of both tagged objects
+            (Self::Tagged(left_key, mut left), Self::Tagged(right_key, right))
+                if left_key == right_key =>
+            {
+                for (value, right) in right {
+                    let left = left.entry(value).or_insert(Self::Bottom);
+                    *left = Self::factor(std::mem::replace(left, Self::Bottom), right);
+                }
+                Self::Tagged(left_key, left)
+            }
+
+            // otherwise the tag is just another field
+            (Self::Tagged(key, variants), other) | (other, Self::Tagged(key, variants)) => {
+                Self::factor(Self::untag(key, variants), other)
+            }
+
             // factor map types
             (Self::Map(left), Self::Map(right)) => Self::Map(Box::new(Self::factor(*left, *right))),
 
+            // an object that wasn't detected as a map in one sample (too few keys)
+            (Self::Map(ty), Self::Object(map, _)) | (Self::Object(map, _), Self::Map(ty)) => {
+                Self::Map(Box::new(Self::factor(
+                    *ty,
+                    Self::fold(map.into_iter().map(|(_, v)| v)),
+                )))
+            }
+
             // factor fields of objects
-            (Self::Object(left), Self::Object(right)) => Self::factor_fields(left, right),
+            (Self::Object(left, left_presence), Self::Object(right, right_presence)) => {
+                let presence = left_presence.merge(right_presence);
+                Self::Object(Self::factor_fields(left, right), presence)
+            }
+
+            // the string didn't always parse as the opaque type
+            (Self::Opaque(..), other) | (other, Self::Opaque(..))
+                if other.kind() == Some(Kind::String) =>
+            {
+                Self::String
+            }
+
+            // anything stays anything
+            (Self::Any, _) | (_, Self::Any) => Self::Any,
+
+            // otherwise keep both of them
+            (left, right) => Self::union(left, right),
+        }
+    }
+
+    /// the kind of a shape. shapes of the same kind can always be factored together,
+    /// the shapes without a kind (e.g. a recursive reference) can't conflict with anything
+    pub(crate) fn kind(&self) -> Option<Kind> {
+        let kind = match self {
+            Self::Bool => Kind::Bool,
+            Self::Integer
+            | Self::Range(..)
+            | Self::Float
+            | Self::BigInteger(..)
+            | Self::BigDecimal => Kind::Number,
+            Self::Time(time) => return time.fallback().kind(),
+            Self::Named(_, shape) => return shape.kind(),
+            Self::String | Self::Enum(..) | Self::Opaque(..) => Kind::String,
+            Self::Array(..) | Self::Tuple(..) => Kind::Array,
+            Self::Object(..) | Self::Map(..) | Self::Tagged(..) => Kind::Object,
+            _ => return None,
+        };
+        Some(kind)
+    }
 
-            // equal opaque types
-            (Self::Opaque(name), ..) | (.., Self::Opaque(name)) => Self::Opaque(name),
+    fn union(left: Self, right: Self) -> Self {
+        fn members(shape: Shape) -> Vec<Shape> {
+            match shape {
+                Shape::Union(members) => members,
+                other => vec![other],
+            }
+        }
 
-            // otherwise anything
-            _ => Self::Any,
+        // the larger side keeps its members, so clustered objects aren't merged together
+        let (mut union, right) = match (members(left), members(right)) {
+            (left, right) if left.len() < right.len() => (right, left),
+            sides => sides,
+        };
+        for right in right {
+            // clustered objects can share a kind, so pick the closest one
+            let closest = union
+                .iter_mut()
+                .filter(|left| left.kind().is_some() && left.kind() == right.kind())
+                .fold(None, |closest: Option<(&mut Self, f64)>, left| {
+                    let score = left.resemblance(&right);
+                    match closest {
+                        Some((_, best)) if best >= score => closest,
+                        _ => Some((left, score)),
+                    }
+                });
+
+            match closest {
+                Some((left, _)) => {
+                    *left = Self::factor(std::mem::replace(left, Self::Bottom), right)
+                }
+                None => union.push(right),
+            }
         }
+
+        union.sort_by_cached_key(Self::specificity);
+        Self::Union(union)
     }
 
-    fn factor_fields(left: Map, mut right: Map) -> Self {
+    /// groups the objects by the keys they share, each group becomes a member of a union
+    fn cluster(shapes: impl IntoIterator<Item = Self>, threshold: f64) -> Self {
+        let mut groups: Vec<Self> = Vec::new();
+        let mut rest = Self::Bottom;
+
+        for shape in shapes {
+            if shape.fields().is_none() {
+                rest = Self::factor(rest, shape);
+                continue;
+            }
+
+            let closest = groups
+                .iter_mut()
+                .map(|group| {
+                    let score = group.resemblance(&shape);
+                    (group, score)
+                })
+                .filter(|(_, score)| *score >= threshold)
+                .fold(
+                    None,
+                    |closest: Option<(&mut Self, f64)>, (group, score)| match closest {
+                        Some((_, best)) if best >= score => closest,
+                        _ => Some((group, score)),
+                    },
+                );
+
+            match closest {
+                Some((group, _)) => {
+                    *group = Self::factor(std::mem::replace(group, Self::Bottom), shape)
+                }
+                None => groups.push(shape),
+            }
+        }
+
+        if groups.len() < 2 {
+            return groups.into_iter().fold(rest, Self::factor);
+        }
+
+        groups.sort_by_cached_key(Self::specificity);
+        Self::factor(Self::Union(groups), rest)
+    }
+
+    /// how alike two shapes of the same kind are, objects are compared by their keys
+    fn resemblance(&self, other: &Self) -> f64 {
+        match (self.fields(), other.fields()) {
+            (Some(left), Some(right)) => similarity(&left, &right),
+            _ => 1.0,
+        }
+    }
+
+    /// keeps every object as its own member of the union, e.g. the `oneOf` of a schema
+    pub(super) fn one_of(shapes: Vec<Self>) -> Self {
+        Self::cluster(shapes, f64::INFINITY)
+    }
+
+    /// the fields of an object, a tagged object also has its tag as a field
+    pub(crate) fn fields(&self) -> Option<Cow<'_, Map>> {
+        match self {
+            Self::Object(map, _) => Some(Cow::Borrowed(map)),
+            Self::Named(_, shape) => shape.fields(),
+            Self::Tagged(key, variants) => match Self::untag(key.clone(), variants.clone()) {
+                Self::Object(map, _) => Some(Cow::Owned(map)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// the order of the members of a union, serde tries the variants of an untagged enum in order
+    /// so the more specific shapes have to come first
+    fn specificity(&self) -> (u8, std::cmp::Reverse<usize>) {
+        let rank = match self.kind() {
+            Some(Kind::Bool) => 0,
+            Some(Kind::Number) => 1,
+            Some(Kind::String) => 2,
+            Some(Kind::Array) => 3,
+            Some(Kind::Object) => 4,
+            None => 5,
+        };
+
+        // objects with more required fields are more specific
+        let required = self.fields().map_or(0, |map| {
+            map.values()
+                .filter(|shape| !matches!(shape, Self::Optional(..) | Self::Null))
+                .count()
+        });
+
+        (rank, std::cmp::Reverse(required))
+    }
+
+    fn factor_fields(left: Map, mut right: Map) -> Map {
         // if the lengths are different we shouldn't unify
         // so we need to track the minimum length we've seen so far
 
         match (left.keys().len(), right.keys().len()) {
-            (_, 0) => return Self::Object(left),
-            (0, _) => return Self::Object(right),
+            (_, 0) => return left,
+            (0, _) => return right,
             // (l, r) if l != r => {
             //     let tup = vec![Self::Object(left), Self::Object(right)];
             //     return Self::Tuple(tup, 2);
@@ -153,10 +611,6 @@ impl Shape {
             _ => {}
         }
 
-        if left == right {
-            return Self::Object(left);
-        }
-
         let mut unified: HashMap<_, _> = left
             .into_iter()
             .map(|(k, v)| {
@@ -169,61 +623,296 @@ impl Shape {
             .collect();
 
         unified.extend(right.into_iter().map(|(k, v)| (k, v.into_optional())));
-        Self::Object(unified)
+        unified
+    }
+
+    /// the variants of an externally tagged object, where every object had a single key that
+    /// names the variant, e.g. `{"Circle": {"r": 1}}` and `{"Square": {"side": 2}}`
+    pub fn external_variants(&self) -> Option<Map> {
+        let (map, presence) = match self {
+            Self::Object(map, presence) => (map, presence),
+            _ => return None,
+        };
+        if map.len() < 2 || presence.seen == 0 || presence.single != presence.seen {
+            return None;
+        }
+
+        let variants = map.iter().map(|(key, shape)| {
+            // the payload is only optional because the other variants didn't have it
+            let (_, null) = presence.keys.get(key).copied().unwrap_or_default();
+            let shape = match shape {
+                Self::Optional(inner) if null == 0 => (**inner).clone(),
+                shape => shape.clone(),
+            };
+            (key.clone(), shape)
+        });
+        Some(variants.collect())
+    }
+
+    /// tuples of different lengths keep their positions if the shorter one lines up
+    /// with the start of the longer one, the missing elements become optional
+    fn factor_tuples(left: Vec<Self>, right: Vec<Self>, arity: u64) -> Self {
+        fn is_mixed(shape: &Shape) -> bool {
+            match shape {
+                Shape::Optional(ty) => is_mixed(ty),
+                shape => matches!(shape, Shape::Any | Shape::Union(..)),
+            }
+        }
+
+        let folded = Self::fold(left.iter().chain(&right).cloned());
+        if !is_mixed(&folded) {
+            return Self::Array(Box::new(folded));
+        }
+
+        let (long, short) = if left.len() >= right.len() {
+            (left, right)
+        } else {
+            (right, left)
+        };
+
+        let mut short = short.into_iter();
+        let shapes = long
+            .into_iter()
+            .map(|left| match short.next() {
+                Some(right) => Self::factor(left, right),
+                None => left.into_optional(),
+            })
+            .collect::<Vec<_>>();
+
+        if shapes.iter().any(is_mixed) {
+            return Self::Array(Box::new(folded));
+        }
+
+        Self::Tuple(shapes, arity)
     }
 
-    pub fn unfold(&self) -> Option<&Self> {
+    /// replaces objects that nest a copy of themselves under the same key with a
+    /// reference back to the outer object, e.g. `{ "children": [{ "children": [] }] }`
+    pub(crate) fn find_recursion(self) -> Self {
+        let recurse = |shape: Box<Self>| Box::new(shape.find_recursion());
+
         match self {
-            Self::Object(obj) => {
-                return obj
-                    .values()
-                    .flat_map(Self::unfold)
-                    .fold(None, |mut left, right| {
-                        match (left, right) {
-                            (Some(Self::Bottom), right) | (None, right) => left = Some(right),
-                            (Some(..), Self::Bottom) => {}
-                            (Some(left), right) if left != right => return None,
-                            _ => {}
-                        };
-                        left
-                    })
-            }
-            Self::Optional(opt) => {
-                if let Self::Array(array) = opt.as_ref() {
-                    let this = array.as_ref();
-                    if matches!(
-                        this,
-                        Self::Null | Self::Bool | Self::String | Self::Integer | Self::Float
-                    ) {
-                        return Some(this);
+            Self::Object(mut map, mut presence) => {
+                let keys = map.keys().cloned().collect::<Vec<_>>();
+                for key in keys {
+                    if is_recursive(&map, &key) {
+                        let (tied, tied_presence) = tie_recursion(map, presence, &key);
+                        map = tied;
+                        presence = tied_presence;
                     }
                 }
+
+                let map = map.into_iter().map(|(k, v)| (k, v.find_recursion()));
+                Self::Object(map.collect(), presence)
             }
-            _ => {}
+            Self::Optional(ty) => Self::Optional(recurse(ty)),
+            Self::Array(ty) => Self::Array(recurse(ty)),
+            Self::Map(ty) => Self::Map(recurse(ty)),
+            Self::Tuple(els, n) => {
+                Self::Tuple(els.into_iter().map(Self::find_recursion).collect(), n)
+            }
+            Self::Tagged(key, variants) => {
+                let variants = variants.into_iter().map(|(k, v)| (k, v.find_recursion()));
+                Self::Tagged(key, variants.collect())
+            }
+            Self::Union(members) => {
+                Self::Union(members.into_iter().map(Self::find_recursion).collect())
+            }
+            Self::Named(name, shape) => Self::Named(name, recurse(shape)),
+            other => other,
+        }
+    }
+
+    /// applies the policy to the values that were seen with different kinds of types,
+    /// every conflict that was resolved by picking one of the types is added to the notes
+    pub(crate) fn unify(
+        self,
+        policy: &UnifyPolicy,
+        path: &mut Vec<String>,
+        notes: &mut Vec<String>,
+    ) -> Result<Self, Conflict> {
+        let mut nested = |shape: Self, segment: String, notes: &mut Vec<String>| {
+            path.push(segment);
+            let shape = shape.unify(policy, path, notes);
+            path.pop();
+            shape
         };
 
-        None
+        let shape = match self {
+            Self::Object(map, presence) => {
+                let mut fields = Map::default();
+                for (key, shape) in map {
+                    let shape = nested(shape, key.clone(), notes)?;
+                    fields.insert(key, shape);
+                }
+                Self::Object(fields, presence)
+            }
+            Self::Tagged(key, variants) => {
+                let mut unified = Map::default();
+                for (value, shape) in variants {
+                    unified.insert(value, shape.unify(policy, path, notes)?);
+                }
+                Self::Tagged(key, unified)
+            }
+            Self::Optional(ty) => ty.unify(policy, path, notes)?.into_optional(),
+            Self::Array(ty) => Self::Array(Box::new(nested(*ty, "[]".into(), notes)?)),
+            Self::Map(ty) => Self::Map(Box::new(nested(*ty, "[*]".into(), notes)?)),
+            Self::Tuple(els, n) => {
+                let els = els
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, el)| nested(el, format!("[{}]", i), notes))
+                    .collect::<Result<_, _>>()?;
+                Self::Tuple(els, n)
+            }
+            Self::Union(members) => {
+                let members = members
+                    .into_iter()
+                    .map(|shape| shape.unify(policy, path, notes))
+                    .collect::<Result<_, _>>()?;
+                Self::resolve(members, policy, path, notes)?
+            }
+            Self::Named(name, shape) => {
+                Self::Named(name, Box::new(shape.unify(policy, path, notes)?))
+            }
+            other => other,
+        };
+        Ok(shape)
     }
 
-    pub fn is_uniform(&self) -> bool {
-        match self {
-            Shape::Object(map) => {
-                let mut ty = None;
-                map.values().all(|left| {
-                    let prev = *ty.get_or_insert(left);
-                    if matches!(prev, Self::Bottom) {
-                        ty.replace(left);
-                        return true;
-                    }
+    fn resolve(
+        mut members: Vec<Self>,
+        policy: &UnifyPolicy,
+        path: &[String],
+        notes: &mut Vec<String>,
+    ) -> Result<Self, Conflict> {
+        // picking a type can remove a conflict with a third type, so start over after each one
+        'resolve: loop {
+            for (i, left) in members.iter().enumerate() {
+                for (j, right) in members.iter().enumerate().skip(i + 1) {
+                    let (left, right) = match (left.kind(), right.kind()) {
+                        (Some(left), Some(right)) if left != right => (left, right),
+                        _ => continue,
+                    };
 
-                    prev == left
-                })
+                    let drop = match policy.resolve(left, right) {
+                        Resolution::Union => continue,
+                        Resolution::Prefer(kind) if kind == left => j,
+                        Resolution::Prefer(kind) if kind == right => i,
+                        Resolution::Prefer(..) => continue,
+                        Resolution::Value => return Ok(Self::Any),
+                        Resolution::Error => {
+                            let path = path.to_vec();
+                            return Err(Conflict { path, left, right });
+                        }
+                    };
+
+                    let kind = if drop == j { left } else { right };
+                    notes.push(format!(
+                        "'{}' was both a {} and a {}, using the {}. the other values need a custom deserializer",
+                        util::json_path(path),
+                        left.name(),
+                        right.name(),
+                        kind.name(),
+                    ));
+                    members.remove(drop);
+                    continue 'resolve;
+                }
             }
-            _ => true,
+            break;
         }
+
+        match members.len() {
+            1 => Ok(members.remove(0)),
+            _ => Ok(Self::Union(members)),
+        }
+    }
+
+    /// merges the variants of a tagged object back into a single object
+    pub fn untag(key: String, variants: Map) -> Self {
+        let mut fields = Map::default();
+        fields.insert(key.clone(), Self::String);
+
+        let mut presence = match Self::fold(variants.into_iter().map(|(_, v)| v)) {
+            Self::Object(map, presence) => {
+                fields.extend(map);
+                presence
+            }
+            Self::Bottom => Presence::default(),
+            other => return other,
+        };
+
+        // the tag was in every object
+        presence.keys.insert(key, (presence.seen, 0));
+
+        Self::Object(fields, presence)
+    }
+
+    fn is_uniform_fields(map: &Map) -> bool {
+        let mut ty = None;
+        map.values().all(|left| {
+            let prev = *ty.get_or_insert(left);
+            if matches!(prev, Self::Bottom) {
+                ty.replace(left);
+                return true;
+            }
+
+            prev.is_alike(left)
+        })
     }
 
-    fn into_optional(self) -> Self {
+    /// whether two shapes have the same structure, ignoring what was observed in them
+    /// (e.g. the values of an enum or the range of an integer)
+    fn is_alike(&self, other: &Self) -> bool {
+        fn all_alike<'a>(
+            left: impl ExactSizeIterator<Item = &'a Shape>,
+            right: impl ExactSizeIterator<Item = &'a Shape>,
+        ) -> bool {
+            left.len() == right.len() && left.zip(right).all(|(l, r)| l.is_alike(r))
+        }
+
+        fn maps_alike(left: &Map, right: &Map) -> bool {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .all(|(key, l)| matches!(right.get(key), Some(r) if l.is_alike(r)))
+        }
+
+        match (self, other) {
+            (Self::Enum(..), Self::Enum(..)) | (Self::Range(..), Self::Range(..)) => true,
+            (Self::Optional(left), Self::Optional(right))
+            | (Self::Array(left), Self::Array(right))
+            | (Self::Map(left), Self::Map(right)) => left.is_alike(right),
+            (Self::Named(left_name, left), Self::Named(right_name, right)) => {
+                left_name == right_name && left.is_alike(right)
+            }
+            (Self::Object(left, _), Self::Object(right, _)) => maps_alike(left, right),
+            (Self::Tagged(left_key, left), Self::Tagged(right_key, right)) => {
+                left_key == right_key && maps_alike(left, right)
+            }
+            (Self::Tuple(left, _), Self::Tuple(right, _)) => all_alike(left.iter(), right.iter()),
+            (Self::Union(left), Self::Union(right)) => all_alike(left.iter(), right.iter()),
+            (left, right) => left == right,
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(
+            self,
+            Self::Bottom
+                | Self::Any
+                | Self::Null
+                | Self::Bool
+                | Self::String
+                | Self::Integer
+                | Self::Float
+                | Self::Opaque(..)
+                | Self::Time(..)
+                | Self::Recursive
+        )
+    }
+
+    pub(super) fn into_optional(self) -> Self {
         match self {
             Self::Bottom | Self::Any | Self::Null | Self::Optional(_) => self,
             other => Self::Optional(Box::new(other)),
@@ -231,14 +920,165 @@ impl Shape {
     }
 }
 
+/// the ratio of the keys that both of the objects have
+pub fn similarity(left: &Map, right: &Map) -> f64 {
+    let shared = left.keys().filter(|k| right.contains_key(*k)).count();
+    match left.len() + right.len() - shared {
+        0 => 1.0,
+        total => shared as f64 / total as f64,
+    }
+}
+
+/// the object under this shape, looking through options and arrays
+fn nested(shape: &Shape) -> Option<&Map> {
+    match shape {
+        Shape::Object(map, _) => Some(map),
+        Shape::Optional(ty) | Shape::Array(ty) => nested(ty),
+        _ => None,
+    }
+}
+
+/// whether the object under `key` has the same key with another object (or nothing) under it,
+/// and mostly the same other keys as its parent
+fn is_recursive(parent: &Map, key: &str) -> bool {
+    fn is_empty(shape: &Shape) -> bool {
+        match shape {
+            Shape::Bottom | Shape::Null => true,
+            Shape::Optional(ty) | Shape::Array(ty) => is_empty(ty),
+            _ => false,
+        }
+    }
+
+    let child = match parent.get(key).and_then(nested) {
+        Some(child) => child,
+        None => return false,
+    };
+
+    if !matches!(child.get(key), Some(ty) if nested(ty).is_some() || is_empty(ty)) {
+        return false;
+    }
+
+    // the key itself is left out, otherwise a single key envelope would look like its child
+    let shared = child
+        .keys()
+        .filter(|k| *k != key && parent.contains_key(*k))
+        .count();
+    let total = parent.len() + child.len() - 2 - shared;
+    shared * 2 >= total
+}
+
+/// folds every level of the nested objects under `key` into one, with the key referring back to it
+fn tie_recursion(map: Map, presence: Presence, key: &str) -> (Map, Presence) {
+    // replaces the nested object with a reference, returning the object
+    fn cut(shape: Shape) -> (Shape, Option<(Map, Presence)>) {
+        match shape {
+            Shape::Object(map, presence) => (Shape::Recursive, Some((map, presence))),
+            Shape::Optional(ty) => {
+                let (ty, map) = cut(*ty);
+                (Shape::Optional(Box::new(ty)), map)
+            }
+            Shape::Array(ty) => {
+                let (ty, map) = cut(*ty);
+                (Shape::Array(Box::new(ty)), map)
+            }
+            other => (other, None),
+        }
+    }
+
+    let mut levels = Vec::new();
+    let mut next = Some((map, presence));
+    while let Some((mut level, presence)) = next.take() {
+        if let Some(field) = level.get_mut(key) {
+            let (shape, nested) = cut(std::mem::replace(field, Shape::Bottom));
+            *field = shape;
+            next = nested;
+        }
+        levels.push(Shape::Object(level, presence));
+    }
+
+    match Shape::fold(levels) {
+        Shape::Object(map, presence) => (map, presence),
+        _ => unreachable!(),
+    }
+}
+
+/// the parts of the numbers in the text that were written as integers with too many digits for
+/// the parser to keep, e.g. `123456789012345678901234567890`. the parser drops the digits after
+/// the mantissa overflows, even those of a fraction, so these would look like a decimal
+pub fn integer_literals(text: &str) -> Integers {
+    let bytes = text.as_bytes();
+    let mut integers = Integers::default();
+    let (mut i, mut in_string) = (0, false);
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_string => i += 1,
+            b'"' => in_string = !in_string,
+            b'-' | b'0'..=b'9' if !in_string => {
+                let start = i;
+                while i < bytes.len()
+                    && matches!(bytes[i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                {
+                    i += 1;
+                }
+                let literal = &text[start..i];
+                let digits = literal.trim_start_matches('-');
+                let is_integer = digits.bytes().all(|c| c.is_ascii_digit());
+                // shorter integers always fit in an i64
+                if is_integer && digits.len() >= 19 {
+                    if let Some(num) = json::parse(literal).ok().and_then(|v| v.as_number()) {
+                        integers.insert(num.as_parts());
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    integers
+}
+
+fn is_dynamic_key(key: &str) -> bool {
+    fn is_numeric(key: &str) -> bool {
+        let key = key.strip_prefix('-').unwrap_or(key);
+        !key.is_empty() && key.bytes().all(|c| c.is_ascii_digit())
+    }
+
+    fn is_hash(key: &str) -> bool {
+        key.len() >= 16 && key.bytes().all(|c| c.is_ascii_hexdigit())
+    }
+
+    fn is_date(key: &str) -> bool {
+        key.len() >= 10
+            && key.bytes().take(10).enumerate().all(|(i, c)| match i {
+                4 | 7 => c == b'-',
+                _ => c.is_ascii_digit(),
+            })
+    }
+
+    is_numeric(key) || format::is_uuid(key) || is_hash(key) || is_date(key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    /// an object that was seen once with these fields
+    fn object(fields: &[(&str, Shape)]) -> Shape {
+        let fields = fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        let presence = Presence::new(&fields);
+        Shape::Object(fields, presence)
+    }
     #[test]
     fn unify() {
         use Shape::*;
         assert_eq!(Shape::factor(Bool, Bool), Bool);
-        assert_eq!(Shape::factor(Bool, Integer), Any);
+        assert_eq!(Shape::factor(Bool, Integer), Union(vec![Bool, Integer]));
         assert_eq!(Shape::factor(Integer, Float), Float);
         assert_eq!(Shape::factor(Null, Any), Any);
         assert_eq!(Shape::factor(Null, Bool), Optional(Box::new(Bool)));
@@ -256,7 +1096,431 @@ mod tests {
                 Optional(Box::new(Shape::String)),
                 Optional(Box::new(Integer))
             ),
-            Any
+            Optional(Box::new(Union(vec![Integer, Shape::String])))
+        );
+    }
+
+    #[test]
+    fn unify_unions() {
+        use Shape::*;
+        assert_eq!(
+            Shape::fold(vec![Integer, Shape::String, Float, Bool]),
+            Union(vec![Bool, Float, Shape::String])
+        );
+        assert_eq!(
+            Shape::factor(Union(vec![Integer, Shape::String]), Null),
+            Optional(Box::new(Union(vec![Integer, Shape::String])))
+        );
+        assert_eq!(Shape::factor(Union(vec![Integer, Bool]), Any), Any);
+    }
+
+    #[test]
+    fn unify_fields() {
+        use Shape::*;
+        let left = object(&[("a", Integer), ("b", Bool)]);
+        let right = object(&[("a", Float), ("c", Null)]);
+
+        let expected = object(&[("a", Float), ("b", Optional(Box::new(Bool))), ("c", Null)]);
+        let unified = Shape::factor(left.clone(), right);
+        assert_eq!(unified, expected);
+
+        let presence = match Shape::factor(unified, left) {
+            Object(_, presence) => presence,
+            _ => unreachable!(),
+        };
+        assert_eq!(presence.seen, 3);
+        assert_eq!(presence.keys["a"], (3, 0));
+        assert_eq!(presence.keys["b"], (2, 0));
+        assert_eq!(presence.keys["c"], (1, 1));
+    }
+
+    #[test]
+    fn cluster_objects() {
+        use Shape::*;
+        let keys = |keys: &[&str]| {
+            let fields = keys.iter().map(|k| (*k, Integer)).collect::<Vec<_>>();
+            object(&fields)
+        };
+        let click = keys(&["id", "x", "y"]);
+        let view = keys(&["url", "duration"]);
+
+        let shapes = vec![view.clone(), click.clone(), view.clone(), Null];
+        assert_eq!(
+            Shape::cluster(shapes.clone(), 0.5),
+            Optional(Box::new(Union(vec![click.clone(), view.clone()])))
+        );
+        // a close enough object joins the group it is closest to
+        let clustered = Shape::factor(Shape::cluster(shapes, 0.5), keys(&["id", "x"]));
+        let partial = Shape::factor(click.clone(), keys(&["id", "x"]));
+        assert_eq!(
+            clustered,
+            Optional(Box::new(Union(vec![partial, view.clone()])))
+        );
+        // similar objects are still merged
+        assert_eq!(
+            Shape::cluster(vec![click.clone(), keys(&["id", "x"])], 0.5),
+            Shape::factor(click, keys(&["id", "x"]))
+        );
+    }
+
+    #[test]
+    fn external_variants() {
+        use Shape::*;
+        let circle = object(&[("Circle", object(&[("r", Float)]))]);
+        let square = object(&[("Square", Integer)]);
+
+        let variants = Shape::factor(circle.clone(), square.clone())
+            .external_variants()
+            .unwrap();
+        assert_eq!(variants["Circle"], object(&[("r", Float)]));
+        assert_eq!(variants["Square"], Integer);
+
+        // a null payload stays optional
+        let variants = Shape::fold(vec![
+            circle.clone(),
+            square.clone(),
+            object(&[("Square", Null)]),
+        ])
+        .external_variants()
+        .unwrap();
+        assert_eq!(variants["Square"], Optional(Box::new(Integer)));
+
+        // an object with more than one key isn't a variant
+        let both = object(&[("Circle", Integer), ("Square", Integer)]);
+        assert_eq!(
+            Shape::fold(vec![circle.clone(), both]).external_variants(),
+            None
+        );
+        assert_eq!(circle.external_variants(), None);
+    }
+
+    #[test]
+    fn unify_policy() {
+        use Shape::*;
+        let unify = |shape: Shape, policy: &UnifyPolicy| {
+            let mut notes = vec![];
+            let shape = shape.unify(policy, &mut vec!["id".into()], &mut notes);
+            (shape, notes.len())
+        };
+        let mixed = Shape::fold(vec![Bool, Integer, Shape::String]);
+
+        let default = UnifyPolicy::default();
+        assert_eq!(unify(mixed.clone(), &default), (Ok(mixed.clone()), 0));
+
+        let widen = UnifyPolicy {
+            rules: vec![
+                (Kind::String, Kind::Number, Resolution::Prefer(Kind::String)),
+                (Kind::Bool, Kind::Number, Resolution::Prefer(Kind::Number)),
+            ],
+            fallback: Resolution::Union,
+        };
+        // the bool goes to the number first, which then goes to the string
+        assert_eq!(unify(mixed.clone(), &widen), (Ok(Shape::String), 2));
+        assert_eq!(
+            unify(Optional(Box::new(mixed.clone())), &widen),
+            (Ok(Optional(Box::new(Shape::String))), 2)
+        );
+
+        let value = UnifyPolicy {
+            fallback: Resolution::Value,
+            ..UnifyPolicy::default()
+        };
+        assert_eq!(
+            unify(Array(Box::new(mixed.clone())), &value).0,
+            Ok(Array(Box::new(Any)))
+        );
+
+        let strict = UnifyPolicy {
+            fallback: Resolution::Error,
+            ..UnifyPolicy::default()
+        };
+        let conflict = Conflict {
+            path: vec!["id".into(), "[]".into()],
+            left: Kind::Bool,
+            right: Kind::Number,
+        };
+        assert_eq!(unify(Array(Box::new(mixed)), &strict).0, Err(conflict));
+    }
+
+    #[test]
+    fn unify_named() {
+        use Shape::*;
+        let named = |name: &str, shape| Named(name.into(), Box::new(shape));
+
+        assert_eq!(
+            Shape::factor(named("Id", Integer), named("Id", Float)),
+            named("Id", Float)
+        );
+        assert_eq!(
+            Shape::factor(named("Id", Integer), Null),
+            Optional(Box::new(named("Id", Integer)))
+        );
+        // a different shape loses the name
+        assert_eq!(Shape::factor(named("Id", Integer), Float), Float);
+
+        // every named object is kept as its own member
+        let card = named("Card", object(&[("number", Integer)]));
+        let bank = named("Bank", object(&[("iban", Integer)]));
+        assert_eq!(
+            Shape::one_of(vec![card.clone(), bank.clone(), Shape::String]),
+            Union(vec![Shape::String, card, bank])
+        );
+    }
+
+    #[test]
+    fn unify_enums() {
+        use Shape::*;
+        let set = |values: &[&str]| values.iter().map(ToString::to_string).collect::<Set>();
+
+        assert_eq!(
+            Shape::factor(Enum(set(&["a"]), 1, 3), Enum(set(&["a"]), 1, 3)),
+            Enum(set(&["a"]), 2, 3)
+        );
+        assert_eq!(
+            Shape::factor(Enum(set(&["a"]), 1, 3), Enum(set(&["b", "a"]), 2, 3)),
+            Enum(set(&["a", "b"]), 3, 3)
+        );
+        assert_eq!(
+            Shape::factor(Null, Enum(set(&["a"]), 1, 3)),
+            Optional(Box::new(Enum(set(&["a"]), 1, 3)))
+        );
+        assert_eq!(Shape::factor(String, Enum(set(&["a"]), 1, 3)), String);
+        // more values than the limit is just a string
+        assert_eq!(
+            Shape::fold(
+                ["a", "b", "c", "d", "a"]
+                    .iter()
+                    .map(|v| Enum(set(&[v]), 1, 3))
+            ),
+            String
+        );
+        assert_eq!(
+            Shape::fold(vec![Array(Box::new(Enum(set(&["a"]), 1, 3))); 3]),
+            Array(Box::new(Enum(set(&["a"]), 3, 3)))
+        );
+    }
+
+    #[test]
+    fn unify_ranges() {
+        use Shape::*;
+        assert_eq!(
+            Shape::fold(vec![Range(3, 3), Range(-1, -1), Range(300, 300)]),
+            Range(-1, 300)
+        );
+        assert_eq!(Shape::factor(Range(1, 2), Float), Float);
+        assert_eq!(
+            Shape::factor(Null, Range(1, 2)),
+            Optional(Box::new(Range(1, 2)))
+        );
+    }
+
+    #[test]
+    fn unify_big_numbers() {
+        use Shape::*;
+        assert_eq!(
+            Shape::fold(vec![Integer, BigInteger(false), Null]),
+            Optional(Box::new(BigInteger(false)))
+        );
+        assert_eq!(
+            Shape::factor(Range(-1, 2), BigInteger(false)),
+            BigInteger(true)
+        );
+        assert_eq!(Shape::factor(BigInteger(false), Float), BigDecimal);
+        let unsigned = || Range(0, u64::MAX as i128);
+        assert_eq!(Shape::factor(Integer, unsigned()), BigInteger(true));
+        assert_eq!(Shape::factor(Range(0, 1), unsigned()), unsigned());
+        assert_eq!(Shape::factor(unsigned(), Float), BigDecimal);
+        assert_eq!(Shape::factor(Integer, BigDecimal), BigDecimal);
+        assert_eq!(
+            Shape::factor(Bool, BigDecimal),
+            Union(vec![Bool, BigDecimal])
+        );
+    }
+
+    #[test]
+    fn big_numbers() {
+        use Shape::*;
+        let opts = crate::Options {
+            precision: Precision::Wide,
+            ..crate::tests::options()
+        };
+        let u64_max = u64::MAX as i128;
+        let tests = &[
+            ("9223372036854775807", Integer),
+            (
+                "9223372036854775808",
+                Range(i64::MAX as i128 + 1, i64::MAX as i128 + 1),
+            ),
+            ("18446744073709551615", Range(u64_max, u64_max)),
+            ("18446744073709551616", BigInteger(false)),
+            ("123456789012345678901234567890", BigInteger(false)),
+            ("-123456789012345678901234567890", BigInteger(true)),
+            ("-9223372036854775808", Integer),
+            ("-9223372036854775809", BigInteger(true)),
+            ("10000000000000000000.0", BigDecimal),
+            ("123456789012345678901.5", BigDecimal),
+            ("12345678901234567890123e2", BigDecimal),
+            ("0.12345678901234567", BigDecimal),
+            ("1.5", Float),
+        ];
+        for (input, expected) in tests {
+            let value = json::parse(input).unwrap();
+            let integers = integer_literals(input);
+            assert_eq!(Shape::new(&value, &opts, &integers), *expected, "{}", input);
+        }
+
+        // only numbers written as integers, and not the ones in strings
+        let text = r#"{"a": "123456789012345678901", "b\"": [-123456789012345678901, 1.5e300]}"#;
+        let integers = integer_literals(text);
+        assert_eq!(integers.len(), 1);
+        let value = json::parse("-123456789012345678901").unwrap();
+        let parts = value.as_number().unwrap().as_parts();
+        assert!(integers.contains(&parts));
+    }
+
+    #[test]
+    fn unify_opaque() {
+        use Shape::*;
+        let uuid = || Opaque("uuid::Uuid".into());
+        assert_eq!(
+            Shape::fold(vec![uuid(), uuid(), Null]),
+            Optional(Box::new(uuid()))
         );
+        assert_eq!(
+            Shape::fold(vec![uuid(), Shape::String, uuid()]),
+            Shape::String
+        );
+        assert_eq!(
+            Shape::factor(uuid(), Opaque("url::Url".into())),
+            Shape::String
+        );
+        assert_eq!(Shape::factor(uuid(), Integer), Union(vec![Integer, uuid()]));
+    }
+
+    #[test]
+    fn unify_times() {
+        use crate::infer::Time::{Date, Milliseconds, Rfc3339, Seconds};
+        use Shape::*;
+        assert_eq!(
+            Shape::fold(vec![Time(Rfc3339), Null, Time(Rfc3339)]),
+            Optional(Box::new(Time(Rfc3339)))
+        );
+        assert_eq!(Shape::factor(Time(Rfc3339), Time(Date)), Shape::String);
+        assert_eq!(Shape::factor(Time(Seconds), Time(Milliseconds)), Integer);
+        assert_eq!(Shape::factor(Time(Seconds), Range(1, 2)), Integer);
+        assert_eq!(Shape::factor(Time(Seconds), Float), Float);
+    }
+
+    #[test]
+    fn unify_tagged() {
+        use Shape::*;
+        let tagged = |value: &str, fields: &[(&str, Shape)]| {
+            Tagged(
+                "type".into(),
+                vec![(value.into(), object(fields))].into_iter().collect(),
+            )
+        };
+
+        let circle = tagged("circle", &[("r", Float)]);
+        let square = tagged("square", &[("side", Integer)]);
+        let expected = vec![
+            ("circle".into(), object(&[("r", Float)])),
+            ("square".into(), object(&[("side", Integer)])),
+        ];
+        assert_eq!(
+            Shape::fold(vec![circle.clone(), square, circle.clone()]),
+            Tagged("type".into(), expected.into_iter().collect())
+        );
+
+        assert_eq!(
+            Shape::factor(circle, object(&[("r", Integer)])),
+            object(&[("type", Optional(Box::new(String))), ("r", Float)])
+        );
+    }
+
+    #[test]
+    fn unify_tuples() {
+        use Shape::*;
+        let tuple = |els: &[Shape]| Tuple(els.to_vec(), 1);
+
+        // trailing elements can be missing
+        assert_eq!(
+            Shape::fold(vec![
+                tuple(&[Integer, String]),
+                tuple(&[Integer, String, Bool]),
+            ]),
+            Tuple(vec![Integer, String, Optional(Box::new(Bool))], 2)
+        );
+
+        // an empty array doesn't lose the positions
+        assert_eq!(
+            Shape::fold(vec![tuple(&[Integer, String]), Array(Box::new(Bottom))]),
+            tuple(&[Optional(Box::new(Integer)), Optional(Box::new(String))])
+        );
+
+        // the same type in every position is just an array
+        assert_eq!(
+            Shape::fold(vec![tuple(&[Float, Float]), tuple(&[Float, Float, Float])]),
+            Array(Box::new(Float))
+        );
+
+        // the positions don't line up
+        assert_eq!(
+            Shape::fold(vec![
+                tuple(&[Integer, String]),
+                tuple(&[String, Integer, Bool])
+            ]),
+            Array(Box::new(Union(vec![Bool, Integer, String])))
+        );
+    }
+
+    #[test]
+    fn find_recursion() {
+        use Shape::*;
+
+        let leaf = object(&[("name", String), ("children", Array(Box::new(Bottom)))]);
+        let node = object(&[("name", String), ("children", Array(Box::new(leaf)))]);
+        let tree = object(&[("name", String), ("children", Array(Box::new(node)))]);
+        assert_eq!(
+            tree.find_recursion(),
+            object(&[("name", String), ("children", Array(Box::new(Recursive)))])
+        );
+
+        let reply = object(&[("text", String)]);
+        let comment = object(&[("text", String), ("reply", reply)]);
+        assert_eq!(
+            object(&[("text", String), ("reply", comment)]).find_recursion(),
+            object(&[("text", String), ("reply", Optional(Box::new(Recursive)))])
+        );
+
+        // the nested object doesn't nest itself again
+        let wrapper = object(&[("data", object(&[("data", Integer)]))]);
+        assert_eq!(wrapper.clone().find_recursion(), wrapper);
+
+        // an envelope isn't part of the recursion, only the objects under it
+        let node = |data| object(&[("name", String), ("data", data)]);
+        let envelope = object(&[("data", node(node(Null)))]);
+        assert_eq!(
+            envelope.find_recursion(),
+            object(&[("data", node(Optional(Box::new(Recursive))))])
+        );
+    }
+
+    #[test]
+    fn dynamic_keys() {
+        for key in &[
+            "12345",
+            "-1",
+            "0b9f5c3e-0d6a-4a7e-9a52-3f1c2b7d8e90",
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+            "2020-10-17",
+            "2020-10-17T12:00:00Z",
+        ] {
+            assert!(is_dynamic_key(key), "{}", key);
+        }
+
+        for key in &["id", "name", "n12345", "created_at", "deadbeef", "2020-1-1"] {
+            assert!(!is_dynamic_key(key), "{}", key);
+        }
     }
 }
@@ -1,4 +1,6 @@
+use anyhow::Context as _;
 use indexmap::IndexSet;
+use infer::Time;
 use std::io::{BufReader, BufWriter, Read, Write};
 
 mod infer;
@@ -13,11 +15,54 @@ where
     R: Read + ?Sized,
     W: Write + ?Sized,
 {
-    let mut reader = BufReader::new(read);
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
+    generate_many(opts, std::iter::once(read), write)
+}
 
-    let program = Program::generate(json::parse(&buf)?, &buf, &opts);
+/// generates a single set of types from several samples
+///
+/// every sample is unified into one shape, so fields not present in all of
+/// the samples will be optional
+pub fn generate_many<'r, I, R, W>(opts: Options, reads: I, write: &mut W) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'r mut R>,
+    R: Read + ?Sized + 'r,
+    W: Write + ?Sized,
+{
+    let bufs = reads
+        .into_iter()
+        .map(|read| {
+            let mut buf = String::new();
+            BufReader::new(read).read_to_string(&mut buf).map(|_| buf)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut values = Vec::new();
+    let mut sample = None;
+
+    for (i, buf) in bufs.iter().enumerate() {
+        let parsed = match opts.input_mode {
+            InputMode::Json | InputMode::Schema => json::parse(buf)
+                .map(|doc| (vec![doc], buf.as_str()))
+                .map_err(anyhow::Error::from),
+            InputMode::JsonLines => parse_lines(buf),
+        };
+        let (docs, first) = parsed.with_context(|| format!("invalid sample #{}", i + 1))?;
+
+        values.extend(docs);
+        sample.get_or_insert(first);
+    }
+
+    let sample = sample.context("no samples were provided")?;
+
+    // the parser loses whether a big number was an integer, so it is found in the text
+    let integers = match opts.precision {
+        Precision::Lossy => infer::Integers::default(),
+        _ => bufs
+            .iter()
+            .flat_map(|buf| infer::integer_literals(buf))
+            .collect(),
+    };
+    let program = Program::generate(values, &integers, sample, &opts)?;
 
     let mut writer = BufWriter::new(write);
     program.print(&mut writer, &opts)?;
@@ -25,8 +70,30 @@ where
     Ok(())
 }
 
+// returns each parsed line along with the first line, which is used as the sample
+fn parse_lines(buf: &str) -> anyhow::Result<(Vec<json::JsonValue>, &str)> {
+    let mut sample = None;
+    let mut values = Vec::new();
+
+    for (i, line) in buf.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let value = json::parse(line).with_context(|| format!("invalid json on line {}", i + 1))?;
+        values.push(value);
+        sample.get_or_insert(line);
+    }
+
+    let sample = sample.context("no json documents were found in the input")?;
+    Ok((values, sample))
+}
+
 #[derive(Debug)]
 pub struct Options {
+    pub input_mode: InputMode,
+
     pub json_name: Option<String>,
     pub root_name: String,
 
@@ -34,15 +101,302 @@ pub struct Options {
     pub make_main: bool,
 
     pub collapse_option_vec: bool,
+    pub detect_recursion: bool,
 
     pub tuple_max: Option<usize>,
 
+    pub integer_policy: IntegerPolicy,
+    pub precision: Precision,
+    pub unify_policy: UnifyPolicy,
+
+    pub infer_maps: bool,
+    pub map_threshold: usize,
+
+    pub merge_similar: Option<f64>,
+    pub cluster: Option<f64>,
+    pub flatten_shared: Option<usize>,
+    pub generics: bool,
+
+    pub required_threshold: Option<f64>,
+    pub presence_comments: bool,
+    pub double_option: bool,
+
+    pub string_formats: Vec<(StringFormat, String)>,
+    pub dates: Option<DateLibrary>,
+
+    pub enum_limit: Option<usize>,
+    pub enum_other: bool,
+
+    pub tag_keys: Vec<String>,
+    pub external_tags: bool,
+
     pub default_derives: String,
     pub field_naming: CasingScheme,
     pub struct_naming: CasingScheme,
 
     pub vec_wrapper: Wrapper,
     pub map_wrapper: Wrapper,
+
+    pub placeholder: Option<String>,
+
+    pub overrides: Vec<Override>,
+}
+
+/// settings for a single node, addressed by its json path, e.g. `$.data.items[].created_at`
+///
+/// array elements are `[]`, map values are `[*]` and tuple elements are `[0]`, `[1]`, ...
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Override {
+    pub path: String,
+    /// use this type for the node
+    pub kind: Option<String>,
+    /// the name of the type generated for the node
+    pub name: Option<String>,
+    /// the name of the field for the node
+    pub rename: Option<String>,
+    /// treat the object as a map
+    pub map: bool,
+    /// keep the node as a `serde_json::Value`
+    pub raw: bool,
+}
+
+impl Override {
+    fn matches(&self, path: &str) -> bool {
+        // the leading `$` is optional
+        let trim = |path: &str| {
+            path.trim_start_matches('$')
+                .trim_start_matches('.')
+                .to_string()
+        };
+        trim(&self.path) == trim(path)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputMode {
+    /// a single json document
+    Json,
+    /// one json document per line (NDJSON / JSON Lines)
+    JsonLines,
+    /// a json schema (draft 7 or 2020-12) rather than a sample
+    Schema,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntegerPolicy {
+    /// always use `i64`
+    I64,
+    /// use the smallest type that fits every observed value
+    Smallest,
+    /// use `u64` if every observed value is non-negative, otherwise `i64`
+    Unsigned,
+}
+
+impl IntegerPolicy {
+    fn select(self, min: i128, max: i128) -> &'static str {
+        fn fits<T: std::convert::TryFrom<i128>>(min: i128, max: i128) -> bool {
+            T::try_from(min).is_ok() && T::try_from(max).is_ok()
+        }
+
+        match self {
+            // only the integers that don't fit in an i64 have a range
+            Self::I64 if fits::<i64>(min, max) => "i64",
+            Self::I64 | Self::Unsigned if min >= 0 => "u64",
+            Self::I64 => "i128",
+            Self::Unsigned if fits::<i64>(min, max) => "i64",
+            Self::Unsigned => "i128",
+            Self::Smallest if fits::<u8>(min, max) => "u8",
+            Self::Smallest if fits::<u16>(min, max) => "u16",
+            Self::Smallest if fits::<u32>(min, max) => "u32",
+            Self::Smallest if fits::<u64>(min, max) => "u64",
+            Self::Smallest if fits::<i8>(min, max) => "i8",
+            Self::Smallest if fits::<i16>(min, max) => "i16",
+            Self::Smallest if fits::<i32>(min, max) => "i32",
+            Self::Smallest if fits::<i64>(min, max) => "i64",
+            Self::Smallest => "i128",
+        }
+    }
+}
+
+/// how a value that was seen with different kinds of types is unified
+///
+/// a rule for the pair of kinds wins over the fallback
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnifyPolicy {
+    pub rules: Vec<(Kind, Kind, Resolution)>,
+    pub fallback: Resolution,
+}
+
+impl Default for UnifyPolicy {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            fallback: Resolution::Union,
+        }
+    }
+}
+
+impl UnifyPolicy {
+    fn resolve(&self, left: Kind, right: Kind) -> Resolution {
+        self.rules
+            .iter()
+            .find(|(l, r, _)| (*l, *r) == (left, right) || (*r, *l) == (left, right))
+            .map_or(self.fallback, |(.., resolution)| *resolution)
+    }
+}
+
+/// the kinds of json values that can conflict with each other
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Array => "array",
+            Self::Object => "object",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// keep both types as an untagged enum
+    Union,
+    /// use the type of this kind, if it is one of the two
+    Prefer(Kind),
+    /// use `serde_json::Value`
+    Value,
+    /// fail with the locations of both types in the samples
+    Error,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Precision {
+    /// numbers that don't fit will be an i64 or an f64
+    Lossy,
+    /// use i128 or u128 for big integers. big decimals will still be an f64
+    Wide,
+    /// use `rust_decimal::Decimal` for big numbers
+    Decimal,
+    /// use `serde_json::Number` for big numbers
+    Arbitrary,
+}
+
+impl Precision {
+    fn integer(self, negative: bool) -> &'static str {
+        match self {
+            Self::Lossy => "i64",
+            Self::Wide if negative => "i128",
+            Self::Wide => "u128",
+            Self::Decimal | Self::Arbitrary => self.decimal(),
+        }
+    }
+
+    fn decimal(self) -> &'static str {
+        match self {
+            Self::Lossy | Self::Wide => "f64",
+            Self::Decimal => "Decimal",
+            Self::Arbitrary => "::serde_json::Number",
+        }
+    }
+
+    fn import(self) -> Option<&'static str> {
+        match self {
+            Self::Decimal => Some("rust_decimal::Decimal"),
+            _ => None,
+        }
+    }
+
+    // without these serde will go through an f64 and lose the precision
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Decimal => Some("rust_decimal/serde-arbitrary-precision"),
+            Self::Arbitrary => Some("serde_json/arbitrary_precision"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StringFormat {
+    Uuid,
+    Url,
+    /// either an IPv4 or an IPv6 address
+    Ip,
+    Email,
+}
+
+impl StringFormat {
+    pub fn default_type(self) -> &'static str {
+        match self {
+            Self::Uuid => "uuid::Uuid",
+            Self::Url => "url::Url",
+            Self::Ip => "std::net::IpAddr",
+            Self::Email => "email_address::EmailAddress",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DateLibrary {
+    Chrono,
+    Time,
+}
+
+impl DateLibrary {
+    /// the type to use and the module for `#[serde(with = "...")]`, if one is needed
+    fn select(self, time: Time, optional: bool) -> (&'static str, Option<&'static str>) {
+        const CHRONO_UTC: &str = "chrono::DateTime<chrono::Utc>";
+        const TIME_OFFSET: &str = "time::OffsetDateTime";
+
+        match (self, time, optional) {
+            (Self::Chrono, Time::Rfc3339, _) => (CHRONO_UTC, None),
+            (Self::Chrono, Time::Date, _) => ("chrono::NaiveDate", None),
+            (Self::Chrono, Time::Seconds, false) => (CHRONO_UTC, Some("chrono::serde::ts_seconds")),
+            (Self::Chrono, Time::Seconds, true) => {
+                (CHRONO_UTC, Some("chrono::serde::ts_seconds_option"))
+            }
+            (Self::Chrono, Time::Milliseconds, false) => {
+                (CHRONO_UTC, Some("chrono::serde::ts_milliseconds"))
+            }
+            (Self::Chrono, Time::Milliseconds, true) => {
+                (CHRONO_UTC, Some("chrono::serde::ts_milliseconds_option"))
+            }
+
+            (Self::Time, Time::Rfc3339, false) => (TIME_OFFSET, Some("time::serde::rfc3339")),
+            (Self::Time, Time::Rfc3339, true) => {
+                (TIME_OFFSET, Some("time::serde::rfc3339::option"))
+            }
+            (Self::Time, Time::Date, _) => ("time::Date", None),
+            (Self::Time, Time::Seconds, false) => (TIME_OFFSET, Some("time::serde::timestamp")),
+            (Self::Time, Time::Seconds, true) => {
+                (TIME_OFFSET, Some("time::serde::timestamp::option"))
+            }
+            (Self::Time, Time::Milliseconds, false) => {
+                (TIME_OFFSET, Some("time::serde::timestamp::milliseconds"))
+            }
+            (Self::Time, Time::Milliseconds, true) => (
+                TIME_OFFSET,
+                Some("time::serde::timestamp::milliseconds::option"),
+            ),
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            Self::Chrono => "chrono/serde",
+            Self::Time => "time/serde-well-known",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
@@ -72,7 +426,7 @@ pub fn no_derives() -> String {
 }
 
 pub fn all_std_derives() -> String {
-    custom(&[
+    custom([
         "Clone",
         "Debug",
         "PartialEq",
@@ -110,3 +464,130 @@ where
             a
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the options with the same defaults as the command line
+    pub fn options() -> Options {
+        Options {
+            input_mode: InputMode::Json,
+            json_name: None,
+            root_name: "Root".into(),
+            make_unit_test: false,
+            make_main: false,
+            collapse_option_vec: false,
+            detect_recursion: true,
+            tuple_max: None,
+            integer_policy: IntegerPolicy::I64,
+            precision: Precision::Lossy,
+            unify_policy: UnifyPolicy::default(),
+            infer_maps: true,
+            map_threshold: 32,
+            merge_similar: None,
+            cluster: None,
+            flatten_shared: None,
+            generics: false,
+            required_threshold: None,
+            presence_comments: false,
+            double_option: false,
+            string_formats: vec![],
+            dates: None,
+            enum_limit: None,
+            enum_other: false,
+            tag_keys: vec!["type".into(), "kind".into()],
+            external_tags: true,
+            default_derives: all_std_derives(),
+            field_naming: CasingScheme::Snake,
+            struct_naming: CasingScheme::Pascal,
+            vec_wrapper: Wrapper::std_vec(),
+            map_wrapper: Wrapper::std_map(),
+            placeholder: None,
+            overrides: vec![],
+        }
+    }
+
+    /// the generated code for a sample
+    pub fn generate(json: &str, opts: Options) -> String {
+        let mut out = Vec::new();
+        super::generate(opts, &mut json.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn override_paths() {
+        let at = |path: &str| Override {
+            path: path.into(),
+            ..Override::default()
+        };
+
+        assert!(at("$.data.items[].id").matches("$.data.items[].id"));
+        assert!(at("data.items[].id").matches("$.data.items[].id"));
+        assert!(at("$.data.items[].id").matches("data.items[].id"));
+        assert!(!at("$.data.items[].id").matches("$.data.items.id"));
+        assert!(!at("$.data.items").matches("$.data.items[]"));
+
+        assert!(at("$.scores[*]").matches("$.scores[*]"));
+        assert!(!at("$.scores[*]").matches("$.scores[]"));
+        assert!(at("$.point[0]").matches("$.point[0]"));
+        assert!(!at("$.point[0]").matches("$.point[1]"));
+
+        assert!(at("$").matches("$"));
+        assert!(at("$[]").matches("$[]"));
+        assert!(!at("$").matches("$[]"));
+    }
+
+    #[test]
+    fn many_samples() {
+        let mut first = r#"{"id":1,"name":"a"}"#.as_bytes();
+        let mut second = r#"{"id":2}"#.as_bytes();
+        let mut out = Vec::new();
+        generate_many(options(), vec![&mut first, &mut second], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("pub id: i64,"), "{}", out);
+        assert!(out.contains("pub name: Option<String>,"), "{}", out);
+    }
+
+    #[test]
+    fn integer_types() {
+        use IntegerPolicy::*;
+        let tests = &[
+            (I64, 0, 300, "i64"),
+            (I64, 0, u64::MAX as i128, "u64"),
+            (Smallest, 0, 255, "u8"),
+            (Smallest, 0, 256, "u16"),
+            (Smallest, 0, 65_536, "u32"),
+            (Smallest, 0, u32::MAX as i128 + 1, "u64"),
+            (Smallest, -1, 127, "i8"),
+            (Smallest, -1, 128, "i16"),
+            (Smallest, -32_769, 0, "i32"),
+            (Smallest, i32::MIN as i128 - 1, 0, "i64"),
+            (Smallest, -1, u64::MAX as i128, "i128"),
+            (Unsigned, 0, u64::MAX as i128, "u64"),
+            (Unsigned, -1, 1, "i64"),
+            (Unsigned, i64::MIN as i128 - 1, 0, "i128"),
+        ];
+
+        for (policy, min, max, expected) in tests {
+            let ty = policy.select(*min, *max);
+            assert_eq!(ty, *expected, "{:?}: {}..={}", policy, min, max);
+        }
+    }
+
+    #[test]
+    fn json_lines() {
+        let (values, sample) = parse_lines("\n{\"a\": 1}\n  \n{\"a\": 2}\n").unwrap();
+        assert_eq!(
+            values,
+            vec![json::object! {"a" => 1}, json::object! {"a" => 2}]
+        );
+        assert_eq!(sample, "{\"a\": 1}");
+
+        let err = parse_lines("{\"a\": 1}\n\n{\"a\": }\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid json on line 3");
+
+        assert!(parse_lines("\n  \n").is_err());
+    }
+}
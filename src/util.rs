@@ -52,14 +52,16 @@ pub fn fix_name(name: &str, used: &mut HashSet<String>, casing: CasingScheme) ->
     }
 }
 
-trait WrapperApply {
-    fn apply(&self, item: String) -> String;
-}
-
-#[derive(Clone, Debug)]
-pub struct NestedWrapper {
-    left: Wrapper,
-    right: Wrapper,
+/// joins the segments of a json path, e.g. `$.data.items[].created_at`
+pub fn json_path(segments: &[String]) -> String {
+    let mut path = String::from("$");
+    for segment in segments {
+        if !segment.starts_with('[') {
+            path.push('.');
+        }
+        path.push_str(segment);
+    }
+    path
 }
 
 #[derive(Clone, Debug)]